@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use blockade_recon::wifi;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = wifi::ManagementTag::parse_all(data);
+});
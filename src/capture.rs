@@ -0,0 +1,230 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use pcap::{Active, Capture, Offline, Packet, PacketHeader};
+
+use crate::source::{self, Packet as _};
+
+mod output;
+mod pcapng;
+
+pub use output::{Format, Interface, Output, Rotation};
+
+/// A packet copied out of the capture buffer so that it may outlive the next read
+pub struct OwnedPacket {
+    pub header: PacketHeader,
+    pub data: Vec<u8>
+}
+impl OwnedPacket {
+    fn new(packet: Packet) -> Self {
+        Self {
+            header: *packet.header,
+            data: packet.data.to_vec()
+        }
+    }
+}
+impl source::Packet for OwnedPacket {
+    fn time(&self) -> SystemTime {
+        let ts = self.header.ts;
+        UNIX_EPOCH + Duration::new(ts.tv_sec as u64, ts.tv_usec as u32 * 1000)
+    }
+    fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Where packets are coming from
+pub enum Source {
+    Live {
+        capture: Capture<Active>,
+        /// Where packets are saved, unless saving is turned off
+        output: Option<Output>
+    },
+    Replay(Replay)
+}
+impl source::Source for Source {
+    type Packet = OwnedPacket;
+    type Error = pcap::Error;
+    fn next(&mut self) -> Result<Option<OwnedPacket>, pcap::Error> {
+        match self {
+            Self::Live { capture, output } => {
+                // The packet borrows the capture, which is needed again if the output rotates
+                let packet = match capture.next() {
+                    Err(pcap::Error::NoMorePackets) | Err(pcap::Error::TimeoutExpired) => return Ok(None),
+                    Err(error) => return Err(error),
+                    Ok(packet) => OwnedPacket::new(packet)
+                };
+                if let Some(output) = output {
+                    output.write(capture, &packet)?
+                }
+                Ok(Some(packet))
+            },
+            Self::Replay(replay) => replay.next()
+        }
+    }
+    fn now(&self) -> SystemTime {
+        match self {
+            Self::Live { .. } => SystemTime::now(),
+            Self::Replay(replay) => replay.now()
+        }
+    }
+    fn finished(&self) -> bool {
+        match self {
+            Self::Live { .. } => false,
+            Self::Replay(replay) => replay.finished()
+        }
+    }
+}
+impl Source {
+    /// Make sure every saved packet has reached the disk
+    pub fn flush(&mut self) -> Result<(), pcap::Error> {
+        match self {
+            Self::Live { output: Some(output), .. } => output.flush(),
+            _ => Ok(())
+        }
+    }
+    /// Flush and rotate the capture file when they are due, which should be done regularly even while no packets arrive
+    pub fn tick(&mut self) -> Result<(), pcap::Error> {
+        match self {
+            Self::Live { capture, output: Some(output) } => output.tick(capture),
+            _ => Ok(())
+        }
+    }
+    /// The packets received and dropped according to libpcap, which only counts packets from an interface
+    pub fn stats(&mut self) -> Result<Option<pcap::Stat>, pcap::Error> {
+        match self {
+            Self::Live { capture, .. } => capture.stats().map(Some),
+            Self::Replay(_) => Ok(None)
+        }
+    }
+    /// Roughly how large the file that packets are being saved to is
+    pub fn saved_size(&self) -> Option<u64> {
+        match self {
+            Self::Live { output: Some(output), .. } => Some(output.size()),
+            _ => None
+        }
+    }
+    /// Whether comments can be attached to saved packets
+    pub fn can_annotate(&self) -> bool {
+        match self {
+            Self::Live { output: Some(output), .. } => output.can_annotate(),
+            _ => false
+        }
+    }
+    /// Attach a comment to the next saved packet
+    pub fn annotate(&mut self, comment: String) {
+        if let Self::Live { output: Some(output), .. } = self {
+            output.annotate(comment)
+        }
+    }
+    /// Whether a comment is waiting for the next packet
+    pub fn comment_pending(&self) -> bool {
+        match self {
+            Self::Live { output: Some(output), .. } => output.comment_pending(),
+            _ => false
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speed {
+    RealTime,
+    Fast,
+    Unlimited
+}
+impl Speed {
+    fn multiplier(self) -> Option<u32> {
+        match self {
+            Self::RealTime => Some(1),
+            Self::Fast => Some(10),
+            Self::Unlimited => None
+        }
+    }
+}
+impl std::fmt::Display for Speed {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.multiplier() {
+            Some(multiplier) => write!(f, "{}x", multiplier),
+            None => write!(f, "Max")
+        }
+    }
+}
+
+/// Plays back a capture file, releasing packets at the pace they were originally captured
+pub struct Replay {
+    capture: Capture<Offline>,
+    /// The next packet from the file, held until playback reaches its timestamp
+    pending: Option<OwnedPacket>,
+    speed: Speed,
+    paused: bool,
+    finished: bool,
+    /// The capture time and wall time that playback was last synchronised at
+    sync: Option<(SystemTime, Instant)>,
+    /// The capture time of the last released packet
+    position: Option<SystemTime>
+}
+impl Replay {
+    pub fn new(capture: Capture<Offline>) -> Self {
+        Self {
+            capture,
+            pending: None,
+            speed: Speed::RealTime,
+            paused: false,
+            finished: false,
+            sync: None,
+            position: None
+        }
+    }
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.resync();
+        self.speed = speed
+    }
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+    pub fn toggle_pause(&mut self) {
+        self.resync();
+        self.paused = !self.paused
+    }
+    pub fn finished(&self) -> bool {
+        self.finished && self.pending.is_none()
+    }
+    /// Restart the playback clock from the current position
+    fn resync(&mut self) {
+        let now = self.now();
+        self.sync = self.position.map(|_| (now, Instant::now()))
+    }
+    /// The capture time that playback has reached
+    pub fn now(&self) -> SystemTime {
+        match (self.sync, self.speed.multiplier()) {
+            (Some((time, instant)), Some(multiplier)) if !self.paused && !self.finished() => time + instant.elapsed() * multiplier,
+            _ => self.position.unwrap_or(UNIX_EPOCH)
+        }
+    }
+    fn next(&mut self) -> Result<Option<OwnedPacket>, pcap::Error> {
+        if self.paused {
+            return Ok(None)
+        }
+        if self.pending.is_none() && !self.finished {
+            match self.capture.next() {
+                Err(pcap::Error::NoMorePackets) => self.finished = true,
+                Err(error) => return Err(error),
+                Ok(packet) => self.pending = Some(OwnedPacket::new(packet))
+            }
+        }
+        let time = match &self.pending {
+            Some(packet) => packet.time(),
+            None => return Ok(None)
+        };
+        if self.sync.is_none() {
+            self.sync = Some((time, Instant::now()))
+        }
+        if self.speed == Speed::Unlimited || time <= self.now() {
+            self.position = Some(time);
+            Ok(self.pending.take())
+        } else {
+            Ok(None)
+        }
+    }
+}
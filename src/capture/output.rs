@@ -0,0 +1,194 @@
+use std::{path::{Path, PathBuf}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use pcap::{Active, Capture, Linktype, Packet, Savefile};
+
+use super::{pcapng, OwnedPacket};
+
+/// The longest time that written packets may sit in a buffer before being flushed to disk
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// The timestamp and lengths stored before each packet in a pcap file
+const PCAP_RECORD_HEADER: u64 = 16;
+
+/// When to move on to a new capture file
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rotation {
+    /// The size in bytes that a file may grow to
+    pub size: Option<u64>,
+    /// How long a file may be written to
+    pub duration: Option<Duration>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Pcap,
+    /// Also records the interface and comments on packets
+    Pcapng
+}
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Pcap => "pcap",
+            Self::Pcapng => "pcapng"
+        }
+    }
+}
+
+/// The interface packets are captured on and how it was set up
+#[derive(Debug, Clone)]
+pub struct Interface {
+    pub name: String,
+    pub linktype: Linktype,
+    pub snaplen: u32,
+    pub promiscuous: bool,
+    pub monitor: bool,
+    /// The BPF expression applied to the capture
+    pub filter: Option<String>
+}
+
+enum Writer {
+    Pcap(Savefile),
+    Pcapng(pcapng::Writer)
+}
+impl Writer {
+    fn open(capture: &Capture<Active>, path: &Path, format: Format, interface: &Interface) -> Result<Self, pcap::Error> {
+        Ok(match format {
+            Format::Pcap => Self::Pcap(capture.savefile(path)?),
+            Format::Pcapng => Self::Pcapng(pcapng::Writer::create(path, interface)?)
+        })
+    }
+}
+
+/// Saves live packets to capture files named from a template, rotating between them as they fill up
+///
+/// The template may contain `{time}`, replaced with the UTC time the file was opened, `{interface}` and `{index}`, the number of the file within this run.
+pub struct Output {
+    template: String,
+    interface: Interface,
+    format: Format,
+    rotation: Rotation,
+    writer: Writer,
+    index: u32,
+    /// The bytes written to the current file
+    written: u64,
+    opened: Instant,
+    flushed: Instant,
+    /// A comment to attach to the next packet
+    comment: Option<String>
+}
+impl Output {
+    pub fn new(capture: &Capture<Active>, template: &str, interface: Interface, format: Format, rotation: Rotation) -> Result<Self, pcap::Error> {
+        let index = 0;
+        let writer = Writer::open(capture, &file_name(template, &interface.name, index), format, &interface)?;
+        Ok(Self {
+            template: template.to_string(),
+            interface,
+            format,
+            rotation,
+            writer,
+            index,
+            written: 0,
+            opened: Instant::now(),
+            flushed: Instant::now(),
+            comment: None
+        })
+    }
+    pub fn write(&mut self, capture: &Capture<Active>, packet: &OwnedPacket) -> Result<(), pcap::Error> {
+        if self.rotation.size.map_or(false, |limit| self.written > 0 && self.written + self.record_size(packet) > limit) {
+            self.rotate(capture)?
+        }
+        self.written += match &mut self.writer {
+            Writer::Pcap(savefile) => {
+                savefile.write(&Packet::new(&packet.header, &packet.data));
+                PCAP_RECORD_HEADER + packet.data.len() as u64
+            },
+            Writer::Pcapng(writer) => writer.write(packet, self.comment.as_deref())?
+        };
+        self.comment = None;
+        Ok(())
+    }
+    /// Move on to a new file once the current one has been open too long, and flush packets that have been buffered for too long
+    ///
+    /// This should be called regularly whether or not packets are arriving, so that a quiet capture still reaches the disk.
+    pub fn tick(&mut self, capture: &Capture<Active>) -> Result<(), pcap::Error> {
+        // An empty file is kept rather than replaced by another empty file
+        if self.written > 0 && self.rotation.duration.map_or(false, |limit| self.opened.elapsed() >= limit) {
+            self.rotate(capture)
+        } else if self.flushed.elapsed() >= FLUSH_INTERVAL {
+            self.flush()
+        } else {
+            Ok(())
+        }
+    }
+    fn rotate(&mut self, capture: &Capture<Active>) -> Result<(), pcap::Error> {
+        self.index += 1;
+        let path = file_name(&self.template, &self.interface.name, self.index);
+        // The old file is closed, flushing it, when it is replaced
+        self.writer = Writer::open(capture, &path, self.format, &self.interface)?;
+        self.written = 0;
+        self.opened = Instant::now();
+        self.flushed = Instant::now();
+        Ok(())
+    }
+    /// The size a packet will take up on disk, including the header of its record
+    fn record_size(&self, packet: &OwnedPacket) -> u64 {
+        match self.format {
+            Format::Pcap => PCAP_RECORD_HEADER + packet.data.len() as u64,
+            Format::Pcapng => pcapng::record_size(packet.data.len(), self.comment.as_deref())
+        }
+    }
+    pub fn flush(&mut self) -> Result<(), pcap::Error> {
+        self.flushed = Instant::now();
+        match &mut self.writer {
+            Writer::Pcap(savefile) => savefile.flush(),
+            Writer::Pcapng(writer) => Ok(writer.flush()?)
+        }
+    }
+    /// Roughly how many bytes have been written to the current file
+    pub fn size(&self) -> u64 {
+        self.written
+    }
+    /// Whether the format can store comments
+    pub fn can_annotate(&self) -> bool {
+        self.format == Format::Pcapng
+    }
+    /// Attach a comment to the next packet written
+    pub fn annotate(&mut self, comment: String) {
+        self.comment = Some(comment)
+    }
+    pub fn comment_pending(&self) -> bool {
+        self.comment.is_some()
+    }
+}
+
+/// Fill in the template, avoiding the name of any file that already exists
+fn file_name(template: &str, interface: &str, index: u32) -> PathBuf {
+    let name = template
+        .replace("{time}", &timestamp(SystemTime::now()))
+        .replace("{interface}", interface)
+        .replace("{index}", &index.to_string());
+    let path = PathBuf::from(name);
+    if !path.exists() {
+        return path
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    let extension = path.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+    (1..).map(|n| path.with_file_name(format!("{}-{}{}", stem, n, extension)))
+        .find(|path| !Path::exists(path))
+        .unwrap()
+}
+
+/// Format a time as a UTC timestamp that is safe to use in file names, such as `20210314-015926`
+fn timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+    // Convert days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
@@ -0,0 +1,243 @@
+use std::{fs::File, io::{self, BufWriter, Write}, path::Path};
+
+use super::{Interface, OwnedPacket};
+
+const SECTION_HEADER_BLOCK: u32 = 0x0a0d_0d0a;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 1;
+const ENHANCED_PACKET_BLOCK: u32 = 6;
+/// Written in the file's byte order so that readers can detect it
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+const OPT_COMMENT: u16 = 1;
+const SHB_USERAPPL: u16 = 4;
+const IF_NAME: u16 = 2;
+const IF_TSRESOL: u16 = 9;
+const IF_FILTER: u16 = 11;
+
+/// Writes packets in the pcapng format, which unlike pcap can describe the interface and carry comments
+pub struct Writer<W: Write = BufWriter<File>> {
+    file: W
+}
+impl Writer {
+    pub fn create(path: &Path, interface: &Interface) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), interface)
+    }
+}
+impl<W: Write> Writer<W> {
+    /// Start a section for the interface, writing the headers
+    pub fn new(file: W, interface: &Interface) -> io::Result<Self> {
+        let mut writer = Self { file };
+
+        let mut section = vec![];
+        section.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        section.extend_from_slice(&1u16.to_le_bytes());
+        section.extend_from_slice(&0u16.to_le_bytes());
+        // The section length is not known while it is still being written
+        section.extend_from_slice(&(-1i64).to_le_bytes());
+        options(&mut section, &[(SHB_USERAPPL, concat!("blockade-recon ", env!("CARGO_PKG_VERSION")).as_bytes())]);
+        writer.block(SECTION_HEADER_BLOCK, section)?;
+
+        let mut description = vec![];
+        description.extend_from_slice(&(interface.linktype.0 as u16).to_le_bytes());
+        description.extend_from_slice(&0u16.to_le_bytes());
+        description.extend_from_slice(&interface.snaplen.to_le_bytes());
+        let mut modes = vec![];
+        if interface.promiscuous {
+            modes.push("promiscuous mode")
+        }
+        if interface.monitor {
+            modes.push("monitor mode")
+        }
+        let comment = if modes.is_empty() {
+            "Captured without promiscuous or monitor mode".to_string()
+        } else {
+            format!("Captured in {}", modes.join(" and "))
+        };
+        // Filters are given as a string, marked by a leading zero
+        let filter = interface.filter.as_ref().map(|filter| [&[0], filter.as_bytes()].concat());
+        let mut description_options: Vec<(u16, &[u8])> = vec![
+            (IF_NAME, interface.name.as_bytes()),
+            // Timestamps are in microseconds, as given by libpcap
+            (IF_TSRESOL, &[6]),
+            (OPT_COMMENT, comment.as_bytes())
+        ];
+        if let Some(filter) = &filter {
+            description_options.push((IF_FILTER, filter));
+        }
+        options(&mut description, &description_options);
+        writer.block(INTERFACE_DESCRIPTION_BLOCK, description)?;
+        Ok(writer)
+    }
+    /// Write a packet, attaching a comment to it if given, returning the size of the record on disk
+    pub fn write(&mut self, packet: &OwnedPacket, comment: Option<&str>) -> io::Result<u64> {
+        let header = &packet.header;
+        let timestamp = header.ts.tv_sec as u64 * 1_000_000 + header.ts.tv_usec as u64;
+        self.write_data(timestamp, &packet.data, header.len, comment)
+    }
+    /// Write captured data with its timestamp in microseconds and the length of the original packet
+    fn write_data(&mut self, timestamp: u64, data: &[u8], length: u32, comment: Option<&str>) -> io::Result<u64> {
+        let mut body = vec![];
+        // The only interface in the section
+        body.extend_from_slice(&0u32.to_le_bytes());
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&length.to_le_bytes());
+        body.extend_from_slice(data);
+        pad(&mut body);
+        if let Some(comment) = comment {
+            options(&mut body, &[(OPT_COMMENT, comment.as_bytes())]);
+        }
+        self.block(ENHANCED_PACKET_BLOCK, body)
+    }
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+    /// Write a block, returning its total length
+    fn block(&mut self, block_type: u32, mut body: Vec<u8>) -> io::Result<u64> {
+        pad(&mut body);
+        // The length is repeated at the end so that the file can be read backwards
+        let length = body.len() as u32 + 12;
+        self.file.write_all(&block_type.to_le_bytes())?;
+        self.file.write_all(&length.to_le_bytes())?;
+        self.file.write_all(&body)?;
+        self.file.write_all(&length.to_le_bytes())?;
+        Ok(length as u64)
+    }
+}
+
+/// The size of the block that a packet of the given length is written in
+pub fn record_size(length: usize, comment: Option<&str>) -> u64 {
+    let padded = |length: usize| ((length + 3) & !3) as u64;
+    // The block type and both lengths, then the interface, timestamp and packet lengths
+    let mut size = 12 + 20 + padded(length);
+    if let Some(comment) = comment {
+        // The option's code and length, and the end of options marker
+        size += 4 + padded(comment.len()) + 4
+    }
+    size
+}
+
+/// Pad a block to a 32-bit boundary
+fn pad(body: &mut Vec<u8>) {
+    body.resize((body.len() + 3) & !3, 0)
+}
+
+/// Append a list of options and the end of options marker
+fn options(body: &mut Vec<u8>, options: &[(u16, &[u8])]) {
+    if options.is_empty() {
+        return
+    }
+    for (code, value) in options {
+        body.extend_from_slice(&code.to_le_bytes());
+        body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        body.extend_from_slice(value);
+        pad(body);
+    }
+    body.extend_from_slice(&[0; 4]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcap::Linktype;
+
+    fn interface(filter: Option<&str>) -> Interface {
+        Interface {
+            name: "wlan0".to_string(),
+            linktype: Linktype(127),
+            snaplen: 65535,
+            promiscuous: false,
+            monitor: true,
+            filter: filter.map(str::to_string)
+        }
+    }
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    }
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+    }
+    /// Split the next block off, checking that both of its lengths agree and that it is padded
+    fn block(data: &[u8]) -> (u32, &[u8], &[u8]) {
+        let length = u32_at(data, 4) as usize;
+        assert_eq!(length % 4, 0);
+        assert_eq!(u32_at(data, length - 4) as usize, length);
+        (u32_at(data, 0), &data[8..length - 4], &data[length..])
+    }
+    /// The options of a block body, checking that each is padded and that the list is terminated
+    fn options(mut data: &[u8]) -> Vec<(u16, &[u8])> {
+        let mut options = vec![];
+        loop {
+            let (code, length) = (u16_at(data, 0), u16_at(data, 2) as usize);
+            if code == 0 {
+                assert_eq!(data, [0; 4]);
+                return options
+            }
+            options.push((code, &data[4..4 + length]));
+            let padded = (length + 3) & !3;
+            assert!(data[4 + length..4 + padded].iter().all(|&byte| byte == 0));
+            data = &data[4 + padded..];
+        }
+    }
+
+    #[test]
+    fn headers() {
+        let writer = Writer::new(vec![], &interface(Some("type mgt"))).unwrap();
+        let (block_type, section, rest) = block(&writer.file);
+        assert_eq!(block_type, SECTION_HEADER_BLOCK);
+        assert_eq!(u32_at(section, 0), BYTE_ORDER_MAGIC);
+        assert_eq!((u16_at(section, 4), u16_at(section, 6)), (1, 0));
+        assert_eq!(&section[8..16], [0xff; 8]);
+        let application = options(&section[16..]);
+        assert_eq!(application.len(), 1);
+        assert_eq!(application[0].0, SHB_USERAPPL);
+
+        let (block_type, description, rest) = block(rest);
+        assert!(rest.is_empty());
+        assert_eq!(block_type, INTERFACE_DESCRIPTION_BLOCK);
+        assert_eq!((u16_at(description, 0), u16_at(description, 2)), (127, 0));
+        assert_eq!(u32_at(description, 4), 65535);
+        assert_eq!(options(&description[8..]), [
+            (IF_NAME, &b"wlan0"[..]),
+            (IF_TSRESOL, &[6][..]),
+            (OPT_COMMENT, &b"Captured in monitor mode"[..]),
+            (IF_FILTER, &b"\0type mgt"[..])
+        ]);
+    }
+
+    #[test]
+    fn packet() {
+        let mut writer = Writer::new(vec![], &interface(None)).unwrap();
+        let headers = writer.file.len();
+        let timestamp = 0x1234_5678_9abc;
+        let size = writer.write_data(timestamp, &[1, 2, 3, 4, 5], 100, None).unwrap();
+        assert_eq!(size, record_size(5, None));
+        assert_eq!(writer.file.len() - headers, size as usize);
+
+        let (block_type, body, rest) = block(&writer.file[headers..]);
+        assert!(rest.is_empty());
+        assert_eq!(block_type, ENHANCED_PACKET_BLOCK);
+        assert_eq!(u32_at(body, 0), 0);
+        assert_eq!((u32_at(body, 4), u32_at(body, 8)), (0x1234, 0x5678_9abc));
+        assert_eq!((u32_at(body, 12), u32_at(body, 16)), (5, 100));
+        assert_eq!(&body[20..], [1, 2, 3, 4, 5, 0, 0, 0]);
+    }
+
+    #[test]
+    fn packet_with_comment() {
+        let mut writer = Writer::new(vec![], &interface(None)).unwrap();
+        let headers = writer.file.len();
+        let size = writer.write_data(0, &[1, 2, 3, 4], 4, Some("found it")).unwrap();
+        assert_eq!(size, record_size(4, Some("found it")));
+        assert_eq!(writer.file.len() - headers, size as usize);
+
+        let (_, body, _) = block(&writer.file[headers..]);
+        assert_eq!(&body[20..24], [1, 2, 3, 4]);
+        assert_eq!(options(&body[24..]), [(OPT_COMMENT, &b"found it"[..])]);
+
+        // A comment that does not fill its last word is padded
+        assert_eq!(writer.write_data(0, &[], 0, Some("odd")).unwrap(), record_size(0, Some("odd")));
+        assert_eq!(record_size(0, Some("odd")), 12 + 20 + 4 + 4 + 4);
+    }
+}
@@ -0,0 +1,1076 @@
+//! Tracks the devices seen in captured frames and what they reveal about themselves
+
+use std::{collections::{HashMap, HashSet, VecDeque}, ops::{Deref, DerefMut}, time};
+use eui48::MacAddress;
+use radiotap::Radiotap;
+use oui::{OuiDatabase, OuiEntry};
+use serde::{Deserialize, Serialize};
+
+use crate::{event, source, wifi};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transmission {
+    pub time: time::SystemTime,
+    /// The signal strength in dBm
+    pub signal: Option<i8>,
+    /// The centre frequency in MHz
+    pub frequency: Option<u16>,
+    /// The data rate in Mbps, missing from sessions saved before it was recorded
+    #[serde(default)]
+    pub rate: Option<f32>
+}
+impl Transmission {
+    fn new(radiotap: Radiotap, time: time::SystemTime) -> Self {
+        Self {
+            time,
+            signal: radiotap.antenna_signal.map(|signal| signal.value),
+            frequency: radiotap.channel.map(|channel| channel.freq),
+            rate: radiotap.rate.map(|rate| rate.value)
+        }
+    }
+    /// The channel the transmission was heard on
+    pub fn channel(&self) -> Option<wifi::Channel> {
+        self.frequency.and_then(wifi::Channel::from_frequency)
+    }
+}
+
+/// The kind of station that an address refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AddressClass {
+    /// Assigned by the manufacturer from its OUI
+    Universal,
+    /// Locally administered, which is nearly always a randomised address that hides the manufacturer
+    Local,
+    /// A group of stations
+    Multicast,
+    /// Every station
+    Broadcast
+}
+impl AddressClass {
+    pub fn of(address: &MacAddress) -> Self {
+        if address.is_broadcast() {
+            Self::Broadcast
+        } else if address.is_multicast() {
+            Self::Multicast
+        } else if address.is_local() {
+            Self::Local
+        } else {
+            Self::Universal
+        }
+    }
+    /// Whether the address belongs to a single physical station
+    pub fn is_individual(self) -> bool {
+        matches!(self, Self::Universal | Self::Local)
+    }
+}
+impl std::fmt::Display for AddressClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Universal => write!(f, "Universal"),
+            Self::Local => write!(f, "Random"),
+            Self::Multicast => write!(f, "Multicast"),
+            Self::Broadcast => write!(f, "Broadcast")
+        }
+    }
+}
+
+/// The part a device plays in the networks around it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Role {
+    /// A station that joins networks
+    Client,
+    AccessPoint,
+    /// A peer in an ad-hoc network with no access point
+    AdHoc,
+    /// A bridge that relays frames with all four addresses between distribution systems
+    Wds,
+    /// A station in an 802.11s mesh
+    Mesh
+}
+impl Role {
+    /// A beacon or probe response for an infrastructure network
+    const CAPABILITY_ESS: u16 = 1 << 0;
+    /// A beacon or probe response for an ad-hoc network
+    const CAPABILITY_IBSS: u16 = 1 << 1;
+    /// The role claimed by a beacon or probe response
+    fn advertised(capabilities: u16, tags: &[wifi::ManagementTag]) -> Option<Self> {
+        if capabilities & Self::CAPABILITY_ESS != 0 {
+            Some(Self::AccessPoint)
+        } else if capabilities & Self::CAPABILITY_IBSS != 0 {
+            Some(Self::AdHoc)
+        } else if tags.iter().any(|tag| matches!(tag, wifi::ManagementTag::MeshId(_))) {
+            Some(Self::Mesh)
+        } else {
+            None
+        }
+    }
+    /// How specific the role is, as a device may take on a more specific role than first seen but not a less specific one
+    ///
+    /// A phone sharing its connection goes from client to access point, and an access point that bridges to another is also a WDS bridge.
+    fn rank(self) -> u8 {
+        match self {
+            Self::Client => 0,
+            Self::AccessPoint | Self::AdHoc => 1,
+            Self::Wds => 2,
+            Self::Mesh => 3
+        }
+    }
+}
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Client => write!(f, "Client"),
+            Self::AccessPoint => write!(f, "AP"),
+            Self::AdHoc => write!(f, "IBSS"),
+            Self::Wds => write!(f, "WDS"),
+            Self::Mesh => write!(f, "Mesh")
+        }
+    }
+}
+impl std::str::FromStr for Role {
+    type Err = ();
+    fn from_str(role: &str) -> Result<Self, ()> {
+        match role.to_lowercase().as_str() {
+            "client" | "station" | "sta" => Ok(Self::Client),
+            "ap" | "accesspoint" => Ok(Self::AccessPoint),
+            "ibss" | "adhoc" => Ok(Self::AdHoc),
+            "wds" => Ok(Self::Wds),
+            "mesh" => Ok(Self::Mesh),
+            _ => Err(())
+        }
+    }
+}
+
+/// A network that a device has searched for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Probe {
+    pub first_seen: time::SystemTime,
+    pub last_seen: time::SystemTime
+}
+
+/// How the real name of a hidden network was discovered
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Uncloak {
+    ProbeResponse,
+    AssociationRequest
+}
+impl std::fmt::Display for Uncloak {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::ProbeResponse => write!(f, "probe response"),
+            Self::AssociationRequest => write!(f, "association request")
+        }
+    }
+}
+
+/// A network name revealed by frames other than the beacon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Uncloaked {
+    pub ssid: String,
+    pub method: Uncloak,
+    pub time: time::SystemTime
+}
+
+/// How far a station has progressed in joining a BSS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AssociationState {
+    Unauthenticated,
+    Authenticated,
+    Associated
+}
+impl std::fmt::Display for AssociationState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Unauthenticated => write!(f, "Unauthenticated"),
+            Self::Authenticated => write!(f, "Authenticated"),
+            Self::Associated => write!(f, "Associated")
+        }
+    }
+}
+
+/// A management frame exchanged between a station and an access point
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum AssociationEvent {
+    Authentication {
+        algorithm: wifi::AuthAlgorithm,
+        sequence: u16,
+        status: u16,
+        from_ap: bool
+    },
+    AssociationRequest {
+        reassociation: bool
+    },
+    AssociationResponse {
+        reassociation: bool,
+        status: u16,
+        aid: u16
+    },
+    /// The reason is None if the frame is protected
+    Disassociation {
+        reason: Option<u16>,
+        from_ap: bool
+    },
+    Deauthentication {
+        reason: Option<u16>,
+        from_ap: bool
+    }
+}
+impl std::fmt::Display for AssociationEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fn sender(from_ap: bool) -> &'static str {
+            if from_ap { "AP" } else { "station" }
+        }
+        fn describe(reason: Option<u16>) -> &'static str {
+            reason.map_or("Reason encrypted", wifi::reason_description)
+        }
+        match *self {
+            Self::Authentication { algorithm, sequence, status, from_ap } =>
+                write!(f, "{} authentication #{} from {}: {}", algorithm, sequence, sender(from_ap), wifi::status_description(status)),
+            Self::AssociationRequest { reassociation: false } => write!(f, "Association request"),
+            Self::AssociationRequest { reassociation: true } => write!(f, "Reassociation request"),
+            Self::AssociationResponse { reassociation, status, .. } =>
+                write!(f, "{} response: {}", if reassociation { "Reassociation" } else { "Association" }, wifi::status_description(status)),
+            Self::Disassociation { reason, from_ap } => write!(f, "Disassociated by {}: {}", sender(from_ap), describe(reason)),
+            Self::Deauthentication { reason, from_ap } => write!(f, "Deauthenticated by {}: {}", sender(from_ap), describe(reason))
+        }
+    }
+}
+
+/// The relationship between a station and a BSS as shown by their management frames
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Association {
+    pub state: AssociationState,
+    /// The association ID given to the station while associated
+    pub aid: Option<u16>,
+    /// The most recent events, oldest first
+    pub history: VecDeque<(time::SystemTime, AssociationEvent)>
+}
+impl Association {
+    /// The number of events to remember for each association
+    const HISTORY_LENGTH: usize = 32;
+    fn new() -> Self {
+        Self {
+            state: AssociationState::Unauthenticated,
+            aid: None,
+            history: VecDeque::new()
+        }
+    }
+    fn record(&mut self, event: AssociationEvent, time: time::SystemTime) {
+        use AssociationEvent::*;
+        match event {
+            Authentication { algorithm, sequence, status: 0, from_ap: true } => {
+                // Shared key authentication takes an extra round trip
+                let last = if algorithm == wifi::AuthAlgorithm::SharedKey { 4 } else { 2 };
+                if sequence == last && self.state == AssociationState::Unauthenticated {
+                    self.state = AssociationState::Authenticated
+                }
+            },
+            AssociationResponse { status: 0, aid, .. } => {
+                self.state = AssociationState::Associated;
+                self.aid = Some(aid)
+            },
+            Disassociation { .. } => if self.state == AssociationState::Associated {
+                self.state = AssociationState::Authenticated;
+                self.aid = None
+            },
+            Deauthentication { .. } => {
+                self.state = AssociationState::Unauthenticated;
+                self.aid = None
+            },
+            _ => ()
+        }
+        if self.history.len() >= Self::HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back((time, event))
+    }
+    fn event(&self, station: MacAddress, bssid: MacAddress, event: AssociationEvent) -> event::Event<'static> {
+        event::Event::Association {
+            station,
+            bssid,
+            state: self.state,
+            aid: self.aid,
+            event
+        }
+    }
+}
+
+/// A device tracked by blockade
+/// Tracks metadata relating to the device
+#[derive(Debug)]
+pub struct KnownDevice {
+    pub manufacturer: Option<OuiEntry>,
+    /// The SSID of the beacon, or None if not a beacon
+    pub beacon: Option<String>,
+    /// Whether the beacon hides its SSID
+    pub hidden: bool,
+    /// The network name learned from other frames sent by this BSSID
+    pub uncloaked: Option<Uncloaked>,
+    /// The security advertised by the beacon
+    pub security: Option<wifi::SecurityProfile>,
+    /// The radio capabilities advertised in the device's management frames
+    pub phy: Option<wifi::PhyProfile>,
+    /// The channel an access point operates on, which may differ from the channel it was heard on
+    pub channel: Option<wifi::Channel>,
+    /// The last transmission from this device, or None if known by reference only
+    pub sent: Option<Transmission>,
+    /// The most recent transmissions from this device, oldest first
+    pub history: VecDeque<Transmission>,
+    /// When this device was first heard transmitting
+    pub first_sent: Option<time::SystemTime>,
+    /// The number of frames heard from this device
+    pub frames: u64,
+    /// The devices that this one has referenced
+    pub knows: HashSet<MacAddress>,
+    /// The SSIDs this device has probed for, which hint at networks it has joined before
+    pub probes: HashMap<String, Probe>,
+    /// The BSSID of the network that a station was last seen taking part in
+    pub bss: Option<MacAddress>,
+    /// How the device builds its probe requests
+    pub fingerprint: Option<wifi::Fingerprint>,
+    /// The part the device plays in its network, as inferred from its frames
+    pub role: Option<Role>
+}
+impl KnownDevice {
+    /// The number of transmissions to remember for each device
+    pub const HISTORY_LENGTH: usize = 128;
+    pub(crate) fn new(address: MacAddress, oui_db: &OuiDatabase) -> Self {
+        Self {
+            // Locally administered addresses are not assigned from an OUI, so any match would be wrong
+            manufacturer: match AddressClass::of(&address) {
+                AddressClass::Universal => oui_db.query_by_mac(&address).unwrap(/* Library should never be able to return an error */),
+                _ => None
+            },
+            beacon: None,
+            hidden: false,
+            uncloaked: None,
+            security: None,
+            phy: None,
+            channel: None,
+            sent: None,
+            history: VecDeque::new(),
+            first_sent: None,
+            frames: 0,
+            knows: HashSet::new(),
+            probes: HashMap::new(),
+            bss: None,
+            fingerprint: None,
+            role: None
+        }
+    }
+    /// The signal strengths of the remembered transmissions that recorded one, oldest first
+    pub fn signals(&self) -> impl Iterator<Item = i8> + '_ {
+        self.history.iter().filter_map(|transmission| transmission.signal)
+    }
+    /// The network name that an access point advertises, using the uncloaked name if its beacon is hidden or has not been heard
+    pub fn ssid(&self) -> Option<&str> {
+        match &self.beacon {
+            Some(beacon) if !self.hidden => Some(beacon),
+            _ => self.uncloaked.as_ref().map(|uncloaked| uncloaked.ssid.as_str())
+        }
+    }
+    fn sent(&mut self, radiotap: Radiotap, time: time::SystemTime) -> &mut Self {
+        let transmission = Transmission::new(radiotap, time);
+        if self.history.len() >= Self::HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(transmission.clone());
+        self.sent = Some(transmission);
+        self.first_sent.get_or_insert(time);
+        self.frames += 1;
+        self
+    }
+    fn knows(&mut self, address: MacAddress) -> &mut Self {
+        if AddressClass::of(&address).is_individual() {
+            self.knows.insert(address);
+        }
+        self
+    }
+    pub(crate) fn beacon(&mut self, ssid: String) -> &mut Self {
+        self.hidden = wifi::hidden_ssid(&ssid);
+        self.beacon = Some(ssid);
+        self
+    }
+    fn secured(&mut self, security: wifi::SecurityProfile) -> &mut Self {
+        self.security = Some(security);
+        self
+    }
+    fn phy(&mut self, tags: &[wifi::ManagementTag], heard: Option<wifi::Channel>) -> &mut Self {
+        let mut phy = wifi::PhyProfile::new(tags);
+        let band = self.channel.or(heard).map(|channel| channel.band);
+        if phy.generation == wifi::Generation::WiFi6 && band == Some(wifi::Band::Ghz6) {
+            phy.generation = wifi::Generation::WiFi6E
+        }
+        self.phy = Some(phy);
+        self
+    }
+    fn operating(&mut self, tags: &[wifi::ManagementTag], heard: Option<wifi::Channel>) -> &mut Self {
+        if let Some(channel) = wifi::Channel::operating(tags, heard) {
+            self.channel = Some(channel)
+        }
+        self
+    }
+    fn uncloak(&mut self, ssid: String, method: Uncloak, time: time::SystemTime) -> &mut Self {
+        self.uncloaked = Some(Uncloaked { ssid, method, time });
+        self
+    }
+    fn probe(&mut self, ssid: String, time: time::SystemTime) -> &mut Self {
+        // Wildcard probes search for any network and say nothing about the device
+        if !ssid.is_empty() {
+            self.probes.entry(ssid)
+                .and_modify(|probe| probe.last_seen = probe.last_seen.max(time))
+                .or_insert(Probe { first_seen: time, last_seen: time });
+        }
+        self
+    }
+    /// Take on a role unless the device is already known to play a more specific one
+    fn acts_as(&mut self, role: Role) -> &mut Self {
+        if self.role.map_or(true, |current| role.rank() >= current.rank()) {
+            self.role = Some(role)
+        }
+        self
+    }
+    fn advertises(&mut self, capabilities: u16, tags: &[wifi::ManagementTag]) -> &mut Self {
+        match Role::advertised(capabilities, tags) {
+            Some(role) => self.acts_as(role),
+            None => self
+        }
+    }
+    fn fingerprinted(&mut self, fingerprint: wifi::Fingerprint) -> &mut Self {
+        self.fingerprint = Some(fingerprint);
+        self
+    }
+    fn member(&mut self, bssid: MacAddress) -> &mut Self {
+        self.bss = Some(bssid);
+        self
+    }
+    /// Follow a station in and out of a BSS through its association frames
+    fn joined(&mut self, bssid: MacAddress, state: AssociationState, event: AssociationEvent) {
+        match event {
+            AssociationEvent::Disassociation { .. } | AssociationEvent::Deauthentication { .. } => if self.bss == Some(bssid) {
+                self.bss = None
+            },
+            _ => if state == AssociationState::Associated {
+                self.bss = Some(bssid)
+            }
+        }
+    }
+    /// Dummy function to consume the unnecessary reference
+    fn done(&mut self) {}
+}
+
+/// Tallies of the frames handled, which show whether a capture is healthy
+#[derive(Debug, Clone, Default)]
+pub struct FrameCounts {
+    pub management: u64,
+    pub control: u64,
+    pub data: u64,
+    pub extension: u64,
+    /// Frames whose radiotap or 802.11 headers could not be parsed
+    pub malformed: u64,
+    /// Frames dropped for failing their FCS check
+    pub corrupted: u64,
+    /// The channel that the most recent frame was heard on
+    pub channel: Option<wifi::Channel>
+}
+impl FrameCounts {
+    /// Every frame handled, including those that were dropped
+    pub fn total(&self) -> u64 {
+        self.management + self.control + self.data + self.extension + self.malformed + self.corrupted
+    }
+    fn count(&mut self, frame_type: &wifi::FrameType) {
+        use wifi::FrameType::*;
+        match frame_type {
+            Management(_) => self.management += 1,
+            Control(_) => self.control += 1,
+            Data(_) => self.data += 1,
+            Extension(_) => self.extension += 1
+        }
+    }
+}
+
+/// A physical device recognised behind several randomised addresses by how it probes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhysicalDevice {
+    pub fingerprint: wifi::Fingerprint,
+    /// The addresses the device has used, in the order they were first seen
+    pub addresses: Vec<MacAddress>,
+    pub first_seen: time::SystemTime,
+    pub last_seen: time::SystemTime,
+    /// The sequence number of the last probe request, which many devices carry on counting across address changes
+    sequence: u16
+}
+impl PhysicalDevice {
+    /// How long a device may go between probes from different addresses and still be recognised
+    const LINK_WINDOW: time::Duration = time::Duration::from_secs(60);
+    /// The furthest sequence numbers may move on between probes from different addresses of the same device
+    const SEQUENCE_GAP: u16 = 64;
+    /// How long the device must have gone unheard before a new address can be its own
+    ///
+    /// A device stops using its old address when it picks a new one, while another device of the same model may be probing alongside it.
+    const QUIET_PERIOD: time::Duration = time::Duration::from_secs(2);
+    fn new(address: MacAddress, fingerprint: wifi::Fingerprint, sequence: u16, time: time::SystemTime) -> Self {
+        Self {
+            fingerprint,
+            addresses: vec![address],
+            first_seen: time,
+            last_seen: time,
+            sequence
+        }
+    }
+    /// How far the sequence number has moved on, if a probe from a new address could have come from this device
+    fn gap(&self, fingerprint: wifi::Fingerprint, sequence: u16, time: time::SystemTime) -> Option<u16> {
+        // Sequence numbers are 12 bits and wrap around
+        let gap = sequence.wrapping_sub(self.sequence) & 0x0fff;
+        let since = time.duration_since(self.last_seen).unwrap_or_default();
+        let quiet = since >= Self::QUIET_PERIOD && since <= Self::LINK_WINDOW;
+        if fingerprint == self.fingerprint && quiet && gap > 0 && gap <= Self::SEQUENCE_GAP {
+            Some(gap)
+        } else {
+            None
+        }
+    }
+    fn probed(&mut self, sequence: u16, time: time::SystemTime) {
+        self.sequence = sequence;
+        self.last_seen = self.last_seen.max(time)
+    }
+}
+
+/// The access points advertising a network name
+#[derive(Debug)]
+pub struct Network<'a> {
+    /// The SSID, or None if it is hidden and has not been uncloaked
+    pub ssid: Option<&'a str>,
+    pub bssids: Vec<(MacAddress, &'a KnownDevice)>
+}
+
+#[derive(Debug)]
+pub struct DeviceList {
+    devices: HashMap<MacAddress, KnownDevice>,
+    /// Associations keyed by station and BSSID
+    associations: HashMap<(MacAddress, MacAddress), Association>,
+    /// The receiver and transmitter of the previous frame if it expects a CTS or ACK, used to attribute responses that only name a receiver
+    last_exchange: Option<(MacAddress, MacAddress)>,
+    /// The physical devices behind randomised addresses
+    physical: Vec<PhysicalDevice>,
+    /// The index into `physical` of each randomised address that has probed
+    linked: HashMap<MacAddress, usize>,
+    pub counts: FrameCounts,
+    /// Where events are reported when running headless
+    pub events: Option<event::Events>,
+    /// The current time according to the capture source
+    pub now: time::SystemTime
+}
+impl DeviceList {
+    fn get_or_default(&mut self, address: MacAddress, oui_db: &OuiDatabase) -> &mut KnownDevice {
+        if !self.devices.contains_key(&address) {
+            let device = KnownDevice::new(address, oui_db);
+            if let Some(events) = &mut self.events {
+                events.emit(event::Event::NewDevice {
+                    address,
+                    manufacturer: device.manufacturer.as_ref().map(|manufacturer| manufacturer.name_short.as_str())
+                })
+            }
+            self.devices.insert(address, device);
+        }
+        self.devices.get_mut(&address).unwrap()
+    }
+    /// Note that a device exists without it having been heard, unless the address is for a group of stations
+    fn reference(&mut self, address: MacAddress, oui_db: &OuiDatabase) {
+        if AddressClass::of(&address).is_individual() {
+            self.get_or_default(address, oui_db);
+        }
+    }
+    /// Report a device's signal strength when headless
+    fn report_signal(&mut self, address: MacAddress, radio: event::Radio) {
+        if let Some(events) = &mut self.events {
+            events.signal(address, radio)
+        }
+    }
+    /// Remember a frame that expects a response, which only frames sent to a single station do
+    fn expect_response(&mut self, receiver: MacAddress, transmitter: MacAddress) {
+        if AddressClass::of(&receiver).is_individual() {
+            self.last_exchange = Some((receiver, transmitter))
+        }
+    }
+    /// Report what is known about a beacon when headless
+    fn report_beacon(&mut self, bssid: MacAddress, radio: event::Radio) {
+        if let (Some(events), Some(device)) = (&mut self.events, self.devices.get(&bssid)) {
+            if let Some(ssid) = &device.beacon {
+                events.beacon(event::Event::Beacon {
+                    bssid,
+                    ssid,
+                    hidden: device.hidden,
+                    channel: device.channel,
+                    security: device.security.as_ref(),
+                    phy: device.phy.as_ref(),
+                    radio
+                })
+            }
+        }
+    }
+    /// Update the tracked devices using a packet read from a capture source
+    ///
+    /// Packets with a malformed radiotap header are counted before the error is returned.
+    pub fn process_packet<P: source::Packet>(&mut self, packet: &P, oui_db: &OuiDatabase) -> Result<(), radiotap::Error> {
+        let (radiotap, data) = match Radiotap::parse(packet.data()) {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                self.counts.malformed += 1;
+                return Err(error)
+            }
+        };
+        self.process(radiotap, data, packet.time(), oui_db);
+        Ok(())
+    }
+    /// Update the tracked devices using a frame captured at the given time
+    pub fn process(&mut self, radiotap: Radiotap, data: &[u8], time: time::SystemTime, oui_db: &OuiDatabase) {
+        let previous = self.last_exchange.take();
+        let radio = event::Radio::new(&radiotap);
+        if let Some(events) = &mut self.events {
+            events.time = time
+        }
+        let (fcs, bad_fcs) = radiotap.flags.as_ref().map_or((false, false), |flags| (flags.fcs, flags.bad_fcs));
+        // Frames that failed the FCS check have unreliable contents, including their addresses
+        let frame = if bad_fcs { Err(wifi::Error::BadFcs) } else { wifi::Frame::new(data, fcs) };
+        if radio.channel.is_some() {
+            self.counts.channel = radio.channel
+        }
+        match &frame {
+            Err(wifi::Error::BadFcs) => {
+                self.counts.corrupted += 1;
+                return
+            },
+            Err(_) => self.counts.malformed += 1,
+            Ok(wifi::Frame { frame_type, .. }) => self.counts.count(frame_type)
+        }
+        if let Ok(wifi::Frame {frame_type, ..}) = frame {
+            use wifi::{FrameType::*, ControlFrame, ManagementFrame, DataFrame, ExtensionFrame};
+            match frame_type {
+                Control(control) => {
+                    let (receiver, transmitter) = control.addresses();
+                    let sender = match (transmitter, previous) {
+                        (Some(transmitter), _) => Some(transmitter),
+                        // CTS and ACK are sent in response to the frame before them
+                        (None, Some((previous_receiver, previous_transmitter))) if previous_transmitter == receiver => Some(previous_receiver),
+                        // A CTS that answers nothing reserves the medium for its sender
+                        (None, _) => if let ControlFrame::Cts { .. } = control { Some(receiver) } else { None }
+                    };
+                    self.reference(receiver, oui_db);
+                    if let Some(sender) = sender.filter(|sender| AddressClass::of(sender).is_individual()) {
+                        let device = self.get_or_default(sender, oui_db).sent(radiotap, time);
+                        if sender != receiver {
+                            device.knows(receiver).done()
+                        }
+                        self.report_signal(sender, radio)
+                    }
+                    if let (ControlFrame::Rts { .. }, Some(transmitter)) = (&control, transmitter) {
+                        self.expect_response(receiver, transmitter)
+                    }
+                }
+                Management(ManagementFrame {
+                    receiver,
+                    transmitter,
+                    bssid,
+                    sequence_control,
+                    fields
+                }) => {
+                    use wifi::{ManagementFields::*, ManagementTag::*};
+                    // Only a single station can transmit, so anything else is a damaged frame
+                    if !AddressClass::of(&transmitter).is_individual() {
+                        return
+                    }
+                    let heard = radiotap.channel.as_ref().and_then(|channel| wifi::Channel::from_frequency(channel.freq));
+                    self.expect_response(receiver, transmitter);
+                    self.reference(receiver, oui_db);
+                    let sender = self.get_or_default(transmitter, oui_db)
+                        .sent(radiotap, time)
+                        .knows(receiver);
+                    let reassociation = matches!(fields, ReassociationRequest { .. });
+                    match fields {
+                        Beacon { ssid, capabilities, tags, .. } => {
+                            sender
+                                .beacon(ssid)
+                                .advertises(capabilities, &tags)
+                                .secured(wifi::SecurityProfile::new(capabilities, &tags))
+                                .operating(&tags, heard)
+                                .phy(&tags, heard)
+                                .done();
+                            self.report_beacon(transmitter, radio)
+                        },
+                        ProbeRequest { ssid, tags, fingerprint } => {
+                            sender.phy(&tags, heard).fingerprinted(fingerprint).acts_as(Role::Client).done();
+                            if let Some(events) = &mut self.events {
+                                events.emit(event::Event::Probe {
+                                    station: transmitter,
+                                    ssid: &ssid,
+                                    phy: self.devices.get(&transmitter).and_then(|device| device.phy.as_ref()),
+                                    fingerprint,
+                                    radio
+                                })
+                            }
+                            self.link(transmitter, fingerprint, sequence_control >> 4, time);
+                            self.get_or_default(transmitter, oui_db).probe(ssid, time).done()
+                        },
+                        ProbeResponse { ssid, capabilities, tags, .. } => {
+                            sender
+                                .advertises(capabilities, &tags)
+                                .secured(wifi::SecurityProfile::new(capabilities, &tags))
+                                .operating(&tags, heard)
+                                .phy(&tags, heard)
+                                .done();
+                            if !wifi::hidden_ssid(&ssid) {
+                                self.get_or_default(bssid, oui_db)
+                                    .uncloak(ssid, Uncloak::ProbeResponse, time)
+                                    .done()
+                            }
+                        },
+                        AssociationRequest { ssid, tags, .. } | ReassociationRequest { ssid, tags, .. } => {
+                            sender.phy(&tags, heard).done();
+                            if !wifi::hidden_ssid(&ssid) {
+                                self.get_or_default(bssid, oui_db)
+                                    .uncloak(ssid, Uncloak::AssociationRequest, time)
+                                    .done()
+                            }
+                            self.associate(transmitter, receiver, bssid, AssociationEvent::AssociationRequest { reassociation }, time)
+                        },
+                        AssociationResponse { reassociation, status, aid, .. } =>
+                            self.associate(transmitter, receiver, bssid, AssociationEvent::AssociationResponse { reassociation, status, aid }, time),
+                        Authentication { algorithm, sequence, status, .. } =>
+                            self.associate(transmitter, receiver, bssid, AssociationEvent::Authentication { algorithm, sequence, status, from_ap: transmitter == bssid }, time),
+                        Disassociation { reason } =>
+                            self.associate(transmitter, receiver, bssid, AssociationEvent::Disassociation { reason, from_ap: transmitter == bssid }, time),
+                        Deauthentication { reason } =>
+                            self.associate(transmitter, receiver, bssid, AssociationEvent::Deauthentication { reason, from_ap: transmitter == bssid }, time)
+                    };
+                    self.report_signal(transmitter, radio)
+                }
+                Data(DataFrame {
+                    to_ds,
+                    from_ds,
+                    receiver,
+                    transmitter,
+                    source,
+                    destination,
+                    bssid,
+                    sequence_control
+                }) => {
+                    if !AddressClass::of(&transmitter).is_individual() {
+                        return
+                    }
+                    self.expect_response(receiver, transmitter);
+                    self.reference(source, oui_db);
+                    if let Some(device) = self.devices.get_mut(&source) {
+                        device.knows(destination).done()
+                    }
+                    self.get_or_default(transmitter, oui_db)
+                        .sent(radiotap, time)
+                        .knows(receiver);
+                    self.report_signal(transmitter, radio);
+                    self.reference(destination, oui_db);
+                    self.reference(receiver, oui_db);
+                    self.infer_roles(to_ds, from_ds, transmitter, receiver, bssid);
+                    if let Some(bssid) = bssid {
+                        // Whichever ends of the frame are not the AP itself are stations in its BSS
+                        for &station in &[transmitter, receiver] {
+                            if station != bssid && !station.is_multicast() {
+                                self.get_or_default(station, oui_db).member(bssid).done()
+                            }
+                        }
+                    }
+                }
+                Extension(_) => {
+                }
+            }
+        }
+    }
+    /// Recognise a randomised address as a physical device seen before by how it probes
+    fn link(&mut self, address: MacAddress, fingerprint: wifi::Fingerprint, sequence: u16, time: time::SystemTime) {
+        // Universal addresses already belong to a single device
+        if AddressClass::of(&address) != AddressClass::Local {
+            return
+        }
+        if let Some(&index) = self.linked.get(&address) {
+            return self.physical[index].probed(sequence, time)
+        }
+        let closest = self.physical.iter()
+            .enumerate()
+            .filter_map(|(index, physical)| physical.gap(fingerprint, sequence, time).map(|gap| (gap, index)))
+            .min();
+        let index = match closest {
+            Some((_, index)) => {
+                let physical = &mut self.physical[index];
+                physical.addresses.push(address);
+                physical.probed(sequence, time);
+                if let Some(events) = &mut self.events {
+                    events.emit(event::Event::Linked {
+                        address,
+                        fingerprint,
+                        addresses: &physical.addresses
+                    })
+                }
+                index
+            },
+            None => {
+                self.physical.push(PhysicalDevice::new(address, fingerprint, sequence, time));
+                self.physical.len() - 1
+            }
+        };
+        self.linked.insert(address, index);
+    }
+    /// Work out the roles of the ends of a data frame from the direction it travels in
+    fn infer_roles(&mut self, to_ds: bool, from_ds: bool, transmitter: MacAddress, receiver: MacAddress, bssid: Option<MacAddress>) {
+        let (sender, recipient) = match (to_ds, from_ds) {
+            (true, false) => (Role::Client, Some(Role::AccessPoint)),
+            (false, true) => (Role::AccessPoint, Some(Role::Client)),
+            // Only bridges and mesh stations send frames with all four addresses
+            (true, true) => (Role::Wds, Some(Role::Wds)),
+            // Stations in an infrastructure network may also talk directly, so only frames in a known ad-hoc network count
+            (false, false) => match bssid.and_then(|bssid| self.devices.get(&bssid)) {
+                Some(KnownDevice { role: Some(Role::AdHoc), .. }) => (Role::AdHoc, None),
+                _ => return
+            }
+        };
+        if let Some(device) = self.devices.get_mut(&transmitter) {
+            device.acts_as(sender).done()
+        }
+        if let (Some(role), Some(device)) = (recipient, self.devices.get_mut(&receiver)) {
+            device.acts_as(role).done()
+        }
+    }
+    /// Record an association event for the station taking part in a management frame
+    fn associate(&mut self, transmitter: MacAddress, receiver: MacAddress, bssid: MacAddress, event: AssociationEvent, time: time::SystemTime) {
+        if let Some(device) = self.devices.get_mut(&bssid) {
+            device.acts_as(Role::AccessPoint).done()
+        }
+        let station = if transmitter == bssid { receiver } else { transmitter };
+        if station.is_multicast() {
+            // An AP may deauthenticate or disassociate all of its stations at once
+            for (&(station, associated), association) in self.associations.iter_mut() {
+                if associated == bssid {
+                    association.record(event, time);
+                    if let Some(device) = self.devices.get_mut(&station) {
+                        device.joined(bssid, association.state, event)
+                    }
+                    if let Some(events) = &mut self.events {
+                        events.emit(association.event(station, bssid, event))
+                    }
+                }
+            }
+            return
+        }
+        let association = self.associations.entry((station, bssid)).or_insert_with(Association::new);
+        association.record(event, time);
+        if let Some(device) = self.devices.get_mut(&station) {
+            device.acts_as(Role::Client).joined(bssid, association.state, event)
+        }
+        if let Some(events) = &mut self.events {
+            events.emit(association.event(station, bssid, event))
+        }
+        if association.state == AssociationState::Associated {
+            // A station may only be associated with a single BSS at a time
+            for ((other_station, other_bssid), other) in self.associations.iter_mut() {
+                if *other_station == station && *other_bssid != bssid && other.state == AssociationState::Associated {
+                    other.state = AssociationState::Authenticated;
+                    other.aid = None
+                }
+            }
+        }
+    }
+    /// Get the associations that a device is part of, as either the station or the BSS, along with the other party
+    pub fn associations(&self, address: MacAddress) -> Vec<(MacAddress, &Association)> {
+        let mut associations: Vec<_> = self.associations.iter().filter_map(|(&(station, bssid), association)| {
+            if station == address {
+                Some((bssid, association))
+            } else if bssid == address {
+                Some((station, association))
+            } else {
+                None
+            }
+        }).collect();
+        associations.sort_by_key(|&(peer, _)| peer);
+        associations
+    }
+    /// Count the devices heard transmitting since a time
+    pub fn active(&self, since: time::SystemTime) -> usize {
+        self.devices.values().filter(|device| device.sent.as_ref().map_or(false, |sent| sent.time >= since)).count()
+    }
+    /// Group the access points by the network they advertise, named networks first
+    ///
+    /// Access points whose beacons have not been heard are included once they answer a probe, are named in an association request or have clients.
+    pub fn networks(&self) -> Vec<Network<'_>> {
+        let served: HashSet<MacAddress> = self.devices.values().filter_map(|device| device.bss).collect();
+        let mut networks: Vec<Network> = vec![];
+        for (&bssid, device) in &self.devices {
+            let access_point = device.beacon.is_some()
+                || device.uncloaked.is_some()
+                || device.role == Some(Role::AccessPoint)
+                || served.contains(&bssid);
+            if !access_point {
+                continue
+            }
+            let ssid = device.ssid();
+            match networks.iter_mut().find(|network| network.ssid == ssid) {
+                Some(network) => network.bssids.push((bssid, device)),
+                None => networks.push(Network { ssid, bssids: vec![(bssid, device)] })
+            }
+        }
+        for network in networks.iter_mut() {
+            network.bssids.sort_by_key(|&(bssid, _)| bssid);
+        }
+        networks.sort_by(|l, r| match (l.ssid, r.ssid) {
+            (Some(l), Some(r)) => l.cmp(r),
+            (l, r) => r.is_some().cmp(&l.is_some())
+        });
+        networks
+    }
+    /// The stations last seen taking part in a BSS
+    pub fn clients(&self, bssid: MacAddress) -> Vec<(MacAddress, &KnownDevice)> {
+        let mut clients: Vec<_> = self.devices.iter()
+            .filter(|(_, device)| device.bss == Some(bssid))
+            .map(|(&address, device)| (address, device))
+            .collect();
+        clients.sort_by_key(|&(address, _)| address);
+        clients
+    }
+    /// The physical devices recognised behind randomised addresses, including those only seen with one address
+    pub fn physical_devices(&self) -> &[PhysicalDevice] {
+        &self.physical
+    }
+    /// The physical device that a randomised address has been recognised as
+    pub fn physical_device(&self, address: &MacAddress) -> Option<&PhysicalDevice> {
+        self.linked.get(address).map(|&index| &self.physical[index])
+    }
+    /// Count the devices heard transmitting, counting each physical device behind randomised addresses once
+    pub fn estimate_devices(&self) -> usize {
+        let heard = self.devices.values().filter(|device| device.sent.is_some()).count();
+        (heard + self.physical.len()).saturating_sub(self.linked.len())
+    }
+    /// Every association as its station, BSSID and state
+    pub(crate) fn all_associations(&self) -> impl Iterator<Item = (MacAddress, MacAddress, &Association)> {
+        self.associations.iter().map(|(&(station, bssid), association)| (station, bssid, association))
+    }
+    /// Remember an association from a previous session
+    pub(crate) fn restore_association(&mut self, station: MacAddress, bssid: MacAddress, association: Association) {
+        self.associations.insert((station, bssid), association);
+    }
+    /// Remember a physical device from a previous session
+    pub(crate) fn restore(&mut self, physical: PhysicalDevice) {
+        let index = self.physical.len();
+        for &address in &physical.addresses {
+            self.linked.insert(address, index);
+        }
+        self.physical.push(physical)
+    }
+}
+impl Default for DeviceList {
+    fn default() -> Self {
+        Self {
+            devices: HashMap::new(),
+            associations: HashMap::new(),
+            last_exchange: None,
+            physical: Vec::new(),
+            linked: HashMap::new(),
+            counts: FrameCounts::default(),
+            events: None,
+            now: time::SystemTime::now()
+        }
+    }
+}
+impl Deref for DeviceList {
+    type Target = HashMap<MacAddress, KnownDevice>;
+    fn deref(&self) -> &Self::Target {
+        &self.devices
+    }
+}
+impl DerefMut for DeviceList {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.devices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FINGERPRINT: wifi::Fingerprint = wifi::Fingerprint(0x1234_5678);
+
+    /// A locally administered address, as used by devices that randomise their address
+    fn random(n: u8) -> MacAddress {
+        MacAddress::new([0x02, 0, 0, 0, 0, n])
+    }
+    fn at(seconds: u64) -> time::SystemTime {
+        time::UNIX_EPOCH + time::Duration::from_secs(1_000_000 + seconds)
+    }
+
+    #[test]
+    fn links_new_address() {
+        let mut devices = DeviceList::default();
+        devices.link(random(1), FINGERPRINT, 100, at(0));
+        devices.link(random(1), FINGERPRINT, 101, at(1));
+        devices.link(random(2), FINGERPRINT, 110, at(20));
+        assert_eq!(devices.physical_devices().len(), 1);
+        let physical = devices.physical_device(&random(2)).unwrap();
+        assert_eq!(physical.addresses, [random(1), random(2)]);
+        assert_eq!((physical.first_seen, physical.last_seen), (at(0), at(20)));
+    }
+
+    #[test]
+    fn sequence_wraps_around() {
+        let mut devices = DeviceList::default();
+        devices.link(random(1), FINGERPRINT, 0x0ffe, at(0));
+        devices.link(random(2), FINGERPRINT, 0x0010, at(10));
+        assert_eq!(devices.physical_devices().len(), 1);
+
+        // Going backwards looks like a gap of nearly the whole sequence space
+        devices.link(random(3), FINGERPRINT, 0x0008, at(20));
+        assert_eq!(devices.physical_devices().len(), 2);
+    }
+
+    #[test]
+    fn sequence_gap_too_large() {
+        let mut devices = DeviceList::default();
+        devices.link(random(1), FINGERPRINT, 100, at(0));
+        devices.link(random(2), FINGERPRINT, 100 + PhysicalDevice::SEQUENCE_GAP + 1, at(10));
+        assert_eq!(devices.physical_devices().len(), 2);
+    }
+
+    #[test]
+    fn link_window() {
+        let mut devices = DeviceList::default();
+        devices.link(random(1), FINGERPRINT, 100, at(0));
+        devices.link(random(2), FINGERPRINT, 101, at(60));
+        assert_eq!(devices.physical_devices().len(), 1);
+        devices.link(random(3), FINGERPRINT, 102, at(121));
+        assert_eq!(devices.physical_devices().len(), 2);
+        assert_eq!(devices.physical_device(&random(3)).unwrap().addresses, [random(3)]);
+    }
+
+    #[test]
+    fn fingerprint_mismatch() {
+        let mut devices = DeviceList::default();
+        devices.link(random(1), FINGERPRINT, 100, at(0));
+        devices.link(random(2), wifi::Fingerprint(0x8765_4321), 101, at(10));
+        assert_eq!(devices.physical_devices().len(), 2);
+    }
+
+    #[test]
+    fn simultaneous_devices_stay_apart() {
+        // Two devices of the same model probing at the same time, with sequence numbers that happen to be close
+        let mut devices = DeviceList::default();
+        devices.link(random(1), FINGERPRINT, 100, at(0));
+        devices.link(random(2), FINGERPRINT, 110, at(1));
+        devices.link(random(1), FINGERPRINT, 101, at(1));
+        devices.link(random(2), FINGERPRINT, 111, at(2));
+        assert_eq!(devices.physical_devices().len(), 2);
+        assert_eq!(devices.physical_device(&random(1)).unwrap().addresses, [random(1)]);
+        assert_eq!(devices.physical_device(&random(2)).unwrap().addresses, [random(2)]);
+    }
+
+    #[test]
+    fn universal_addresses_are_not_linked() {
+        let mut devices = DeviceList::default();
+        devices.link(MacAddress::new([0x00, 0x11, 0x22, 0, 0, 1]), FINGERPRINT, 100, at(0));
+        assert!(devices.physical_devices().is_empty());
+    }
+}
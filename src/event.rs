@@ -0,0 +1,198 @@
+use std::{collections::HashMap, fmt, io::{self, Write}, sync::{Arc, Mutex}, time::{SystemTime, UNIX_EPOCH}};
+use eui48::MacAddress;
+use radiotap::Radiotap;
+use serde::Serialize;
+
+use crate::{wifi, AssociationEvent, AssociationState};
+
+/// How far a device's signal must move from the last reported strength to be reported again
+const SIGNAL_STEP: i8 = 3;
+
+/// The radiotap metadata of the frame behind an event
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Radio {
+    /// The signal strength in dBm
+    pub signal: Option<i8>,
+    /// The centre frequency in MHz
+    pub frequency: Option<u16>,
+    pub channel: Option<wifi::Channel>,
+    /// The data rate in Mbps
+    pub rate: Option<f32>
+}
+impl Radio {
+    pub fn new(radiotap: &Radiotap) -> Self {
+        let frequency = radiotap.channel.as_ref().map(|channel| channel.freq);
+        Self {
+            signal: radiotap.antenna_signal.as_ref().map(|signal| signal.value),
+            frequency,
+            channel: frequency.and_then(wifi::Channel::from_frequency),
+            rate: radiotap.rate.as_ref().map(|rate| rate.value)
+        }
+    }
+}
+
+/// Something worth reporting that was learned from a frame
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    NewDevice {
+        address: MacAddress,
+        manufacturer: Option<&'a str>
+    },
+    Beacon {
+        bssid: MacAddress,
+        ssid: &'a str,
+        hidden: bool,
+        channel: Option<wifi::Channel>,
+        security: Option<&'a wifi::SecurityProfile>,
+        phy: Option<&'a wifi::PhyProfile>,
+        radio: Radio
+    },
+    Probe {
+        station: MacAddress,
+        ssid: &'a str,
+        phy: Option<&'a wifi::PhyProfile>,
+        fingerprint: wifi::Fingerprint,
+        radio: Radio
+    },
+    /// A randomised address was recognised as a physical device that used other addresses before
+    Linked {
+        address: MacAddress,
+        fingerprint: wifi::Fingerprint,
+        /// Every address the device is known to have used, oldest first
+        addresses: &'a [MacAddress]
+    },
+    Association {
+        station: MacAddress,
+        bssid: MacAddress,
+        state: AssociationState,
+        aid: Option<u16>,
+        #[serde(rename = "frame")]
+        event: AssociationEvent
+    },
+    Signal {
+        address: MacAddress,
+        radio: Radio
+    },
+    Error {
+        location: &'a str,
+        message: &'a str,
+        reason: String,
+        /// Whether the program exits because of the error
+        fatal: bool
+    }
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    /// Seconds since the Unix epoch
+    time: f64,
+    #[serde(flatten)]
+    event: Event<'a>
+}
+
+/// A shared handle to where events are written, one JSON object per line
+#[derive(Clone)]
+pub struct Log {
+    out: Arc<Mutex<Box<dyn Write + Send>>>
+}
+impl Log {
+    pub fn new(out: Box<dyn Write + Send>) -> Self {
+        Self {
+            out: Arc::new(Mutex::new(out))
+        }
+    }
+    pub fn write(&self, time: SystemTime, event: Event) -> io::Result<()> {
+        let record = Record {
+            time: time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64(),
+            event
+        };
+        let mut out = self.out.lock().unwrap();
+        serde_json::to_writer(&mut *out, &record)?;
+        out.write_all(b"\n")
+    }
+    pub fn flush(&self) -> io::Result<()> {
+        self.out.lock().unwrap().flush()
+    }
+    /// Report an error that the program cannot recover from, making sure it is written out
+    pub fn error(&mut self, location: String, message: &str, error: &dyn fmt::Display) {
+        self.report(location, message, error, true)
+    }
+    /// Report an error that the program carries on from
+    pub fn warning(&mut self, location: String, message: &str, error: &dyn fmt::Display) {
+        self.report(location, message, error, false)
+    }
+    fn report(&mut self, location: String, message: &str, error: &dyn fmt::Display, fatal: bool) {
+        let event = Event::Error {
+            location: &location,
+            message,
+            reason: error.to_string(),
+            fatal
+        };
+        // Nowhere is left to report a failure to report the error
+        let _ = self.write(SystemTime::now(), event);
+        let _ = self.flush();
+    }
+}
+impl fmt::Debug for Log {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Log").finish()
+    }
+}
+
+/// Writes the events seen while processing frames
+#[derive(Debug)]
+pub struct Events {
+    log: Log,
+    /// The capture time of the frame being processed
+    pub time: SystemTime,
+    /// The last signal strength reported for each device
+    signals: HashMap<MacAddress, i8>,
+    /// What was last reported about each beacon, to avoid repeating it every beacon interval
+    beacons: HashMap<MacAddress, String>,
+    /// The first failure to write an event
+    error: Option<io::Error>
+}
+impl Events {
+    pub fn new(log: Log) -> Self {
+        Self {
+            log,
+            time: UNIX_EPOCH,
+            signals: HashMap::new(),
+            beacons: HashMap::new(),
+            error: None
+        }
+    }
+    pub fn emit(&mut self, event: Event) {
+        if self.error.is_none() {
+            if let Err(error) = self.log.write(self.time, event) {
+                self.error = Some(error)
+            }
+        }
+    }
+    /// Report a device's signal strength if it has changed noticeably
+    pub fn signal(&mut self, address: MacAddress, radio: Radio) {
+        if let Some(signal) = radio.signal {
+            let changed = self.signals.get(&address).map_or(true, |&last| (signal as i16 - last as i16).abs() >= SIGNAL_STEP as i16);
+            if changed {
+                self.signals.insert(address, signal);
+                self.emit(Event::Signal { address, radio })
+            }
+        }
+    }
+    /// Report a beacon if it differs from when it was last reported
+    pub fn beacon(&mut self, event: Event) {
+        if let Event::Beacon { bssid, ssid, hidden, channel, security, phy, .. } = &event {
+            let summary = serde_json::to_string(&(ssid, hidden, channel, security, phy)).unwrap_or_default();
+            if self.beacons.get(bssid) == Some(&summary) {
+                return
+            }
+            self.beacons.insert(*bssid, summary);
+        }
+        self.emit(event)
+    }
+    /// Take the first error encountered while writing events
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
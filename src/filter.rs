@@ -0,0 +1,196 @@
+use std::fmt;
+use eui48::MacAddress;
+
+use blockade_recon::{DeviceList, KnownDevice, Role};
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownTerm(String),
+    InvalidValue(String),
+    UnterminatedQuote
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownTerm(term) => write!(f, "Unknown filter term {:?}", term),
+            Self::InvalidValue(term) => write!(f, "Invalid value in {:?}", term),
+            Self::UnterminatedQuote => write!(f, "Unterminated quote")
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    /// Hex digits that the address must start with
+    Mac(String),
+    Manufacturer(String),
+    /// Matches beacons, uncloaked names and probes
+    Ssid(String),
+    /// The weakest signal in dBm to allow
+    Signal(i8),
+    /// Matches the channel a device was heard on or operates on
+    Channel(u8),
+    Role(Role),
+    Sent
+}
+impl Term {
+    fn parse(term: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidValue(term.to_string());
+        let (key, value) = match term.find(':') {
+            Some(colon) => (&term[..colon], &term[colon + 1..]),
+            None => (term, "")
+        };
+        Ok(match key {
+            "mac" => {
+                let digits: String = value.chars().filter(|c| !matches!(c, ':' | '-' | '.')).collect();
+                if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(invalid())
+                }
+                Self::Mac(digits.to_lowercase())
+            },
+            "vendor" | "manufacturer" if !value.is_empty() => Self::Manufacturer(value.to_lowercase()),
+            "ssid" if !value.is_empty() => Self::Ssid(value.to_lowercase()),
+            "signal" => Self::Signal(value.parse().map_err(|_| invalid())?),
+            "channel" => Self::Channel(value.parse().map_err(|_| invalid())?),
+            "role" => Self::Role(value.parse().map_err(|_| invalid())?),
+            "sent" if value.is_empty() => Self::Sent,
+            "vendor" | "manufacturer" | "ssid" | "sent" => return Err(invalid()),
+            _ => return Err(Error::UnknownTerm(term.to_string()))
+        })
+    }
+    fn matches(&self, address: &MacAddress, device: &KnownDevice) -> bool {
+        match self {
+            Self::Mac(prefix) => {
+                let digits: String = address.as_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+                digits.starts_with(prefix.as_str())
+            },
+            Self::Manufacturer(name) => device.manufacturer.as_ref().map_or(false, |manufacturer| {
+                manufacturer.name_short.to_lowercase().contains(name.as_str())
+                    || manufacturer.name_long.as_ref().map_or(false, |long| long.to_lowercase().contains(name.as_str()))
+            }),
+            Self::Ssid(ssid) => {
+                let contains = |candidate: &str| candidate.to_lowercase().contains(ssid.as_str());
+                device.beacon.as_deref().map_or(false, contains)
+                    || device.uncloaked.as_ref().map_or(false, |uncloaked| contains(&uncloaked.ssid))
+                    || device.probes.keys().any(|probe| contains(probe))
+            },
+            Self::Signal(threshold) => device.sent.as_ref()
+                .and_then(|sent| sent.signal)
+                .map_or(false, |signal| signal >= *threshold),
+            Self::Channel(number) => {
+                let heard = device.sent.as_ref()
+                    .and_then(|sent| sent.frequency)
+                    .and_then(blockade_recon::wifi::Channel::from_frequency);
+                heard.into_iter().chain(device.channel).any(|channel| channel.number == *number)
+            },
+            Self::Role(role) => device.role == Some(*role),
+            Self::Sent => device.sent.is_some()
+        }
+    }
+}
+
+/// Narrows down the devices shown without discarding any
+///
+/// A filter is a list of terms separated by spaces, all of which a device must match:
+/// `mac:<prefix>`, `vendor:<name>`, `ssid:<name>`, `signal:<dBm>`, `channel:<number>`, `role:<role>` and `sent`.
+/// Values containing spaces may be quoted, such as `ssid:"Free WiFi"`.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    text: String,
+    terms: Vec<Term>
+}
+impl Filter {
+    /// Parse a filter, giving None if it has no terms
+    pub fn parse(text: &str) -> Result<Option<Self>, Error> {
+        let mut terms = vec![];
+        let mut term = String::new();
+        let mut quoted = false;
+        for c in text.chars().chain(std::iter::once(' ')) {
+            match c {
+                '"' => quoted = !quoted,
+                c if c.is_whitespace() && !quoted => if !term.is_empty() {
+                    terms.push(Term::parse(&term)?);
+                    term.clear()
+                },
+                c => term.push(c)
+            }
+        }
+        if quoted {
+            return Err(Error::UnterminatedQuote)
+        }
+        Ok(if terms.is_empty() {
+            None
+        } else {
+            Some(Self {
+                text: text.trim().to_string(),
+                terms
+            })
+        })
+    }
+    pub fn matches(&self, address: &MacAddress, device: &KnownDevice) -> bool {
+        self.terms.iter().all(|term| term.matches(address, device))
+    }
+}
+/// The devices that pass the display filter, or every device if there is none
+pub fn visible<'a>(filter: Option<&'a Filter>, devices: &'a DeviceList) -> impl Iterator<Item = (&'a MacAddress, &'a KnownDevice)> {
+    devices.iter().filter(move |(address, device)| filter.map_or(true, |filter| filter.matches(address, device)))
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(text: &str) -> Vec<Term> {
+        Filter::parse(text).unwrap().unwrap().terms
+    }
+
+    #[test]
+    fn empty() {
+        assert!(Filter::parse("").unwrap().is_none());
+        assert!(Filter::parse("  \t ").unwrap().is_none());
+    }
+
+    #[test]
+    fn terms_in_order() {
+        let terms = terms(" mac:AA:bb-0 vendor:Apple signal:-70 channel:6 role:ap sent ");
+        assert!(matches!(&terms[0], Term::Mac(prefix) if prefix == "aabb0"));
+        assert!(matches!(&terms[1], Term::Manufacturer(name) if name == "apple"));
+        assert!(matches!(terms[2], Term::Signal(-70)));
+        assert!(matches!(terms[3], Term::Channel(6)));
+        assert!(matches!(terms[4], Term::Role(Role::AccessPoint)));
+        assert!(matches!(terms[5], Term::Sent));
+        assert_eq!(terms.len(), 6);
+    }
+
+    #[test]
+    fn quoted_value() {
+        let filter = Filter::parse(r#"ssid:"Free WiFi" sent"#).unwrap().unwrap();
+        assert!(matches!(&filter.terms[0], Term::Ssid(ssid) if ssid == "free wifi"));
+        assert!(matches!(filter.terms[1], Term::Sent));
+        assert_eq!(filter.to_string(), r#"ssid:"Free WiFi" sent"#);
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        assert!(matches!(Filter::parse(r#"ssid:"Free WiFi"#), Err(Error::UnterminatedQuote)));
+    }
+
+    #[test]
+    fn unknown_term() {
+        assert!(matches!(Filter::parse("sent bssid:00"), Err(Error::UnknownTerm(term)) if term == "bssid:00"));
+        assert!(matches!(Filter::parse("probes"), Err(Error::UnknownTerm(term)) if term == "probes"));
+    }
+
+    #[test]
+    fn invalid_value() {
+        for text in ["sent:x", "ssid:", "vendor", "mac:xyz", "mac:", "signal:strong", "channel:300", "role:router"].iter() {
+            assert!(matches!(Filter::parse(text), Err(Error::InvalidValue(term)) if term == *text), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,13 @@
+//! The 802.11 parsing and device tracking behind blockade
+//!
+//! Capturing with libpcap is only available with the `capture` feature, and the terminal interface with the `ui` feature.
+
+pub mod wifi;
+pub mod device;
+pub mod source;
+pub mod event;
+pub mod session;
+#[cfg(feature = "capture")]
+pub mod capture;
+
+pub use device::{AddressClass, Association, AssociationEvent, AssociationState, DeviceList, FrameCounts, KnownDevice, Network, PhysicalDevice, Probe, Role, Transmission, Uncloak, Uncloaked};
@@ -1,21 +1,22 @@
-use std::{collections::{HashMap, HashSet}, ops::{Deref, DerefMut}, fs, time};
-use eui48::MacAddress;
+use std::{sync::{Arc, Mutex, mpsc, atomic::{AtomicBool, Ordering}}, fs, io::{self, Write}, path, thread, time};
 use pcap::{Capture, Device};
-use radiotap::Radiotap;
-use oui::{OuiDatabase, OuiEntry};
+use oui::OuiDatabase;
 use clap::{Arg, App};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use blockade_recon::{capture, event, session, source::Source, DeviceList};
 use termion::{event::Key, input::MouseTerminal, raw::IntoRawMode, screen::AlternateScreen};
 use tui::{
     backend::TermionBackend,
-    layout::{Constraint, Direction, Layout},
-    widgets::{Block, Borders, List, ListItem, Tabs},
+    layout::{Alignment, Constraint, Direction, Layout},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Tabs},
     style::{Style, Modifier, Color},
-    text::Spans
+    text::{Span, Spans}
 };
 
 mod ui;
-mod wifi;
 mod page;
+mod sniffer;
+mod filter;
 
 fn main() {
     let args = App::new("Blockade Recon 2")
@@ -28,6 +29,14 @@ fn main() {
                 .long("interface")
                 .help("Don't pick a default wireless interface to sniff traffic on")
         )
+        .arg(
+            Arg::with_name("device")
+                .short("D")
+                .long("device")
+                .help("Sniff traffic on the named wireless interface")
+                .value_name("NAME")
+                .conflicts_with("interface")
+        )
         .arg(
             Arg::with_name("dont_monitor")
                 .short("m")
@@ -41,9 +50,104 @@ fn main() {
                 .help("Specify the path to the OUI database file")
                 .value_name("FILE")
         )
+        .arg(
+            Arg::with_name("read")
+                .short("r")
+                .long("read")
+                .help("Analyse a previously saved capture file instead of sniffing an interface")
+                .value_name("FILE")
+                .conflicts_with_all(&["interface", "device", "dont_monitor"])
+        )
+        .arg(
+            Arg::with_name("filter")
+                .short("f")
+                .long("filter")
+                .help("Only capture packets matching a BPF expression, such as \"wlan type mgt\"")
+                .value_name("EXPRESSION")
+        )
+        .arg(
+            Arg::with_name("write")
+                .short("w")
+                .long("write")
+                .help("Save captured packets to files named from a template, where {time}, {interface} and {index} are filled in")
+                .value_name("PATH")
+                .conflicts_with("read")
+        )
+        .arg(
+            Arg::with_name("no_write")
+                .short("n")
+                .long("no-write")
+                .help("Don't save captured packets")
+                .conflicts_with_all(&["read", "write"])
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .help("The file format to save captured packets in, where pcapng records interface details and comments")
+                .value_name("FORMAT")
+                .possible_values(&["pcap", "pcapng"])
+                .conflicts_with_all(&["read", "no_write"])
+        )
+        .arg(
+            Arg::with_name("rotate_size")
+                .long("rotate-size")
+                .help("Start a new capture file once the current one reaches a size")
+                .value_name("MEGABYTES")
+                .validator(|value| value.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+                .conflicts_with_all(&["read", "no_write"])
+        )
+        .arg(
+            Arg::with_name("rotate_time")
+                .long("rotate-time")
+                .help("Start a new capture file after writing to the current one for a while")
+                .value_name("SECONDS")
+                .validator(|value| value.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+                .conflicts_with_all(&["read", "no_write"])
+        )
+        .arg(
+            Arg::with_name("headless")
+                .long("headless")
+                .help("Report findings as lines of JSON instead of showing the TUI")
+                .conflicts_with("interface")
+        )
+        .arg(
+            Arg::with_name("events")
+                .long("events")
+                .help("Write the JSON reported when headless to a file instead of stdout")
+                .value_name("FILE")
+                .requires("headless")
+        )
+        .arg(
+            Arg::with_name("frame_rate")
+                .long("frame-rate")
+                .help("Redraw the TUI at most this many times per second")
+                .value_name("FPS")
+                .validator(|value| match value.parse::<u32>() {
+                    Ok(0) => Err("The frame rate must be at least 1".to_string()),
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e.to_string())
+                })
+                .conflicts_with("headless")
+        )
+        .arg(
+            Arg::with_name("session")
+                .short("s")
+                .long("session")
+                .help("Resume the session saved in a file, creating it if necessary, and periodically save to it")
+                .value_name("FILE")
+        )
         .get_matches();
 
-    let mut ui = ui::Ui::new();
+    let mut ui = if args.is_present("headless") {
+        let mut ui = Frontend::Headless(event::Log::new(Box::new(io::stdout())));
+        if let Some(path) = args.value_of("events") {
+            let file = expect!(ui => fs::File::create(path), "Unable to create the events file");
+            ui = Frontend::Headless(event::Log::new(Box::new(io::BufWriter::new(file))));
+        }
+        ui
+    } else {
+        Frontend::Tui(ui::Ui::new())
+    };
 
     let oui_db = if let Some(oui_path) = args.value_of("database") {
         let user_db = expect!(ui => fs::read_to_string(oui_path), "Unable to open specified OUI database file");
@@ -52,246 +156,498 @@ fn main() {
         expect!(ui => OuiDatabase::new_from_export(include_bytes!("../manuf")), "Unable to parse default OUI database")
     };
     
-    let device = if args.is_present("interface") {
-        let devices = expect!(ui => Device::list(), "Unable to find devices");
-        let devices_names: Vec<_> = devices.iter().map(|d| ListItem::new(vec![Spans::from(d.name.as_str())])).collect();
-        let list = List::new(devices_names)
-            .block(Block::default().borders(Borders::ALL).title("Select a WiFi Device"))
-            .highlight_style(Style::default().bg(Color::Reset).add_modifier(Modifier::REVERSED))
-            .highlight_symbol("> ");
-        let mut list_state = ui::ListState::with_item_count(devices.len());
-
-        fn draw(ui: &mut ui::Ui, list: &List, list_state: &mut ui::ListState) {
-            expect!(
-                ui =>
-                    ui.terminal.draw(|f| f.render_stateful_widget(list.clone(), f.size(), list_state)), 
-                    "Unable to create list widget"
-            )
+    let (source, source_name) = if let Some(path) = args.value_of("read") {
+        let mut capture = expect!(ui => Capture::from_file(path), "Unable to open capture file");
+        if capture.get_datalink() != pcap::Linktype::IEEE802_11_RADIOTAP {
+            let _: () = expect!(ui => Err(""), "The capture file does not use the radiotap datalink layer required by this program");
         }
-        draw(&mut ui, &list, &mut list_state);
-        'select_device: loop {
-            for key in ui.input.stdin.iter() {
-                match key {
-                    Key::Esc => return,
-                    Key::Up | Key::Char('w') => list_state.up(),
-                    Key::Down | Key::Char('s') => list_state.down(),
-                    Key::PageUp => list_state.top(),
-                    Key::PageDown => list_state.bottom(),
-                    Key::Char('\n') => break 'select_device devices[list_state.selected().unwrap()].clone(),
-                    _ => continue
-                }
-                // Control flow will return after mutably borrowing the ui
-                break
-            }
-            draw(&mut ui, &list, &mut list_state);
+        if let Some(filter) = args.value_of("filter") {
+            expect!(ui => capture.filter(filter), "Unable to apply the capture filter");
         }
+        (capture::Source::Replay(capture::Replay::new(capture)), path.to_string())
     } else {
-        expect!(ui => Device::lookup(), "Unable to choose a default device")
-    };
+        let device = if let Some(name) = args.value_of("device") {
+            Device::from(name)
+        } else if args.is_present("interface") {
+            let devices = expect!(ui => Device::list(), "Unable to find devices");
+            let ui = match &mut ui {
+                Frontend::Tui(ui) => ui,
+                Frontend::Headless(_) => unreachable!("Picking an interface conflicts with running headless")
+            };
+            let devices_names: Vec<_> = devices.iter().map(|d| ListItem::new(vec![Spans::from(d.name.as_str())])).collect();
+            let list = List::new(devices_names)
+                .block(Block::default().borders(Borders::ALL).title("Select a WiFi Device"))
+                .highlight_style(Style::default().bg(Color::Reset).add_modifier(Modifier::REVERSED))
+                .highlight_symbol("> ");
+            let mut list_state = ui::ListState::with_item_count(devices.len());
 
-    let capture = expect!(ui => Capture::from_device(device), "Unable to open capture device")
-        .promisc(true)
-        .rfmon(!args.is_present("dont_monitor"))
-        .immediate_mode(true);
-    let capture = expect!(ui => capture.open(), "Unable to start listening on capture device");
-    let mut capture = expect!(ui => capture.setnonblock(), "Unable to capture packets in a non-blocking fashion");
-    let mut savefile = expect!(ui => capture.savefile("capture.pcap"), "Unable to create save file for packet capture");
+            fn draw(ui: &mut ui::Ui, list: &List, list_state: &mut ui::ListState) {
+                expect!(
+                    ui =>
+                        ui.terminal.draw(|f| f.render_stateful_widget(list.clone(), f.size(), list_state)), 
+                        "Unable to create list widget"
+                )
+            }
+            draw(ui, &list, &mut list_state);
+            'select_device: loop {
+                for key in ui.input.stdin.iter() {
+                    match key {
+                        Key::Esc => return,
+                        Key::Up | Key::Char('w') => list_state.up(),
+                        Key::Down | Key::Char('s') => list_state.down(),
+                        Key::PageUp => list_state.top(),
+                        Key::PageDown => list_state.bottom(),
+                        Key::Char('\n') => break 'select_device devices[list_state.selected().unwrap()].clone(),
+                        _ => continue
+                    }
+                    // Control flow will return after mutably borrowing the ui
+                    break
+                }
+                draw(ui, &list, &mut list_state);
+            }
+        } else {
+            expect!(ui => Device::lookup(), "Unable to choose a default device")
+        };
+
+        let name = device.name.clone();
+        let monitor = !args.is_present("dont_monitor");
+        let capture = expect!(ui => Capture::from_device(device), "Unable to open capture device")
+            .promisc(true)
+            .rfmon(monitor)
+            .snaplen(SNAPLEN)
+            .immediate_mode(true);
+        let capture = expect!(ui => capture.open(), "Unable to start listening on capture device");
+        let mut capture = expect!(ui => capture.setnonblock(), "Unable to capture packets in a non-blocking fashion");
 
-    if capture.get_datalink() != pcap::Linktype::IEEE802_11_RADIOTAP {
-        let mut ok = false;
-        for datalink in expect!(ui => capture.list_datalinks(), "Unable to determine supported datalink layers") {
-            if datalink == pcap::Linktype::IEEE802_11_RADIOTAP {
-                ok = true;
-                expect!(ui => capture.set_datalink(datalink), "Unable to set the datalink layer")
+        if capture.get_datalink() != pcap::Linktype::IEEE802_11_RADIOTAP {
+            let mut ok = false;
+            for datalink in expect!(ui => capture.list_datalinks(), "Unable to determine supported datalink layers") {
+                if datalink == pcap::Linktype::IEEE802_11_RADIOTAP {
+                    ok = true;
+                    expect!(ui => capture.set_datalink(datalink), "Unable to set the datalink layer")
+                }
+            }
+            if !ok {
+                let _: () = expect!(ui => Err(""), "The interface does not support the radiotap datalink layer required by this program");
             }
         }
-        if !ok {
-            let _: () = expect!(ui => Err(""), "The interface does not support the radiotap datalink layer required by this program");
+        // The filter is compiled for the datalink layer so must be applied after it is chosen
+        if let Some(filter) = args.value_of("filter") {
+            expect!(ui => capture.filter(filter), "Unable to apply the capture filter");
         }
-    }
+        let output = if args.is_present("no_write") {
+            None
+        } else {
+            let rotation = capture::Rotation {
+                size: args.value_of("rotate_size").map(|size| size.parse::<u64>().unwrap() * 1_000_000),
+                duration: args.value_of("rotate_time").map(|seconds| time::Duration::from_secs(seconds.parse().unwrap()))
+            };
+            let format = if args.value_of("format") == Some("pcapng") { capture::Format::Pcapng } else { capture::Format::Pcap };
+            let template = args.value_of("write").map(String::from).unwrap_or_else(|| format!("{}.{}", DEFAULT_WRITE_TEMPLATE, format.extension()));
+            let interface = capture::Interface {
+                name: name.clone(),
+                linktype: capture.get_datalink(),
+                snaplen: SNAPLEN as u32,
+                promiscuous: true,
+                monitor,
+                filter: args.value_of("filter").map(String::from)
+            };
+            Some(expect!(ui => capture::Output::new(&capture, &template, interface, format, rotation), "Unable to create save file for packet capture"))
+        };
+        (capture::Source::Live { capture, output }, name)
+    };
 
     let mut devices = DeviceList::default();
-    let pages: &mut [&mut dyn page::Page] = &mut [&mut page::Devices::new(), &mut page::Manufacturers::new()];
+    let session_file = args.value_of("session").map(path::Path::new);
+    if let Some(session_file) = session_file {
+        expect!(ui => session::load(session_file, &mut devices, &oui_db), "Unable to load session");
+    }
+    // The TUI's raw mode turns Ctrl-C into a key press, but signals may still come from elsewhere
+    let terminate = Arc::new(AtomicBool::new(false));
+    for &signal in &[SIGINT, SIGTERM] {
+        expect!(ui => signal_hook::flag::register(signal, Arc::clone(&terminate)), "Unable to listen for termination signals");
+    }
+    match ui {
+        Frontend::Tui(ui) => {
+            let frame_rate = args.value_of("frame_rate").map_or(DEFAULT_FRAME_RATE, |rate| rate.parse().unwrap());
+            sniff(ui, source, source_name, devices, oui_db, session_file, &terminate, frame_rate)
+        },
+        Frontend::Headless(log) => headless(log, source, devices, &oui_db, session_file, &terminate)
+    }
+}
+
+/// Sniff packets while showing what has been found in the TUI
+fn sniff(mut ui: ui::Ui, source: capture::Source, source_name: String, devices: DeviceList, oui_db: OuiDatabase, session_file: Option<&path::Path>, terminate: &AtomicBool, frame_rate: u32) {
+    let can_annotate = source.can_annotate();
+    let devices = Arc::new(Mutex::new(devices));
+    let sniffer = sniffer::Sniffer::spawn(source, Arc::clone(&devices), oui_db);
+    let frame_interval = time::Duration::from_secs(1) / frame_rate;
+    let started = time::Instant::now();
+    let mut last_save = time::Instant::now();
+    let mut last_draw: Option<time::Instant> = None;
+    // The frame count that the packet rate was last measured from
+    let mut rate_sample: Option<(time::Instant, u64)> = None;
+    let mut packet_rate = 0.0;
+    let mut changed = true;
+    let mut status: Option<sniffer::Status> = None;
+    let pages: &mut [&mut dyn page::Page] = &mut [&mut page::Devices::new(), &mut page::Networks::new(), &mut page::Manufacturers::new()];
     let mut tabs = ui::TabState::new(pages.iter().map(|p| Spans::from(p.name())).collect());
+    let mut prompt: Option<(Prompting, ui::Prompt)> = None;
+    // Limits the devices shown without affecting what is tracked
+    let mut display_filter: Option<filter::Filter> = None;
+    let mut filter_error = None;
+    // Explains why a key did nothing, until the next key is pressed
+    let mut notice = None;
+    // Takes over the whole screen while tracking down a device
+    let mut hunt: Option<page::Hunt> = None;
     'sniff: loop {
+        if terminate.load(Ordering::Relaxed) {
+            break 'sniff
+        }
         for key in ui.input.stdin.try_iter() {
+            changed = true;
+            notice = None;
+            if let Some((prompting, input)) = &mut prompt {
+                match input.key(key) {
+                    ui::PromptEvent::Editing => (),
+                    ui::PromptEvent::Submit(text) => {
+                        match prompting {
+                            Prompting::Comment => {
+                                let _ = sniffer.commands.send(sniffer::Command::Annotate(text));
+                            },
+                            Prompting::DisplayFilter => match filter::Filter::parse(&text) {
+                                Ok(filter) => {
+                                    display_filter = filter;
+                                    filter_error = None
+                                },
+                                Err(error) => filter_error = Some(error)
+                            }
+                        }
+                        prompt = None
+                    },
+                    ui::PromptEvent::Cancel => prompt = None
+                }
+                continue
+            }
+            if let Some(tracking) = &mut hunt {
+                match key {
+                    Key::Esc => hunt = None,
+                    Key::Ctrl('c') => break 'sniff,
+                    Key::Char('b') => tracking.toggle_bell(),
+                    _ => ()
+                }
+                continue
+            }
             match key {
-                Key::Esc => break 'sniff,
+                Key::Esc | Key::Ctrl('c') => break 'sniff,
                 Key::F(i) => tabs.select(i as usize),
                 Key::Char('\t') => tabs.next(),
                 Key::Up | Key::Char('w') => pages[tabs.index].up(),
                 Key::Down | Key::Char('s') => pages[tabs.index].down(),
                 Key::PageUp => pages[tabs.index].top(),
                 Key::PageDown => pages[tabs.index].bottom(),
+                Key::Left | Key::Char('a') => pages[tabs.index].left(),
+                Key::Right | Key::Char('d') => pages[tabs.index].right(),
+                Key::Char('r') => pages[tabs.index].reverse(),
+                Key::Char('f') => if let Some(target) = pages[tabs.index].selected() {
+                    hunt = Some(page::Hunt::new(target))
+                },
+                Key::Char('c') => if can_annotate {
+                    prompt = Some((Prompting::Comment, ui::Prompt::new("Comment on the next packet")))
+                } else {
+                    notice = Some("Comments need packets to be saved with --format pcapng")
+                },
+                Key::Char('/') => {
+                    let text = display_filter.as_ref().map(|filter| filter.to_string()).unwrap_or_default();
+                    prompt = Some((Prompting::DisplayFilter, ui::Prompt::with_text("Display filter", text)))
+                },
+                Key::Char(' ') => {
+                    let _ = sniffer.commands.send(sniffer::Command::TogglePause);
+                },
+                Key::Char('1') => {
+                    let _ = sniffer.commands.send(sniffer::Command::SetSpeed(capture::Speed::RealTime));
+                },
+                Key::Char('2') => {
+                    let _ = sniffer.commands.send(sniffer::Command::SetSpeed(capture::Speed::Fast));
+                },
+                Key::Char('3') => {
+                    let _ = sniffer.commands.send(sniffer::Command::SetSpeed(capture::Speed::Unlimited));
+                },
                 _ => ()
             }
         }
 
-        expect!(
-            ui =>
-                ui.terminal.draw(|frame| {
-                    let areas = Layout::default()
-                        .direction(Direction::Vertical)
-                        .margin(0)
-                        .constraints([Constraint::Length(2), Constraint::Min(0)])
-                        .split(frame.size());
-                    frame.render_widget(
-                        Tabs::new(tabs.titles.clone())
-                            .block(Block::default().borders(Borders::BOTTOM))
-                            .select(tabs.index)
-                            .style(Style::reset())
-                            .highlight_style(Style::reset().add_modifier(Modifier::BOLD | Modifier::REVERSED)),
-                        areas[0]
-                    );
-                    pages[tabs.index].render(frame, areas[1], &mut devices)
-                }),
-                "Unable to draw to stdout"
-        );
-
-        match capture.next() {
-            Err(pcap::Error::NoMorePackets) | Err(pcap::Error::TimeoutExpired) => (),
-            Err(error) => expect!(ui => Err(error), "Unable to get next packet"),
-            Ok(packet) => {
-                savefile.write(&packet);
-        
-                let (radiotap, data) = expect!(ui => Radiotap::parse(packet.data), "Unable to parse radiotap header");
-                if let Ok(wifi::Frame {frame_type, ..}) = wifi::Frame::new(data) {
-                    use wifi::{FrameType::*, ControlFrame, ManagementFrame, DataFrame, ExtensionFrame};
-                    match frame_type {
-                        Control(ControlFrame::Ack) => {
-
-                        }
-                        Management(ManagementFrame {
-                            receiver,
-                            transmitter,
-                            bssid,
-                            sequence_control,
-                            fields
-                        }) => {
-                            use wifi::{ManagementFields::*, ManagementTag::*};
-                            devices.get_or_default(receiver, &oui_db);
-                            let sender = devices.get_or_default(transmitter, &oui_db)
-                                .sent(radiotap)
-                                .knows(receiver);
-                            match fields {
-                                Beacon { ssid, ..} => sender.beacon(ssid).done(),
-                                _ => ()
-                            };
-                        }
-                        Data(DataFrame {
-                            receiver,
-                            transmitter,
-                            source,
-                            destination,
-                            bssid,
-                            sequence_control
-                        }) => {
-                            devices.get_or_default(source, &oui_db)
-                                .knows(destination);
-                            devices.get_or_default(transmitter, &oui_db)
-                                .sent(radiotap)
-                                .knows(receiver);
-                            devices.get_or_default(destination, &oui_db);
-                            devices.get_or_default(receiver, &oui_db);
+        // Redraw when something has changed, and every so often anyway to keep relative times current, but never above the frame rate
+        let due = last_draw.map_or(true, |last| {
+            let elapsed = last.elapsed();
+            elapsed >= frame_interval && (changed || elapsed >= REFRESH_INTERVAL)
+        });
+        if due {
+            let mut devices = devices.lock().unwrap();
+            if let Some(status) = &status {
+                devices.now = status.now;
+            }
+            let total = devices.counts.total();
+            match rate_sample {
+                Some((sampled, _)) if sampled.elapsed() < RATE_INTERVAL => (),
+                Some((sampled, count)) => {
+                    packet_rate = (total - count) as f64 / sampled.elapsed().as_secs_f64();
+                    rate_sample = Some((time::Instant::now(), total))
+                },
+                None => rate_sample = Some((time::Instant::now(), total))
+            }
+            let status_bar = status_bar(&source_name, &devices, status.as_ref(), packet_rate, started.elapsed());
+            let mut text = vec![];
+            if let Some(notice) = notice {
+                text.push(notice.to_string());
+            }
+            if let Some(error) = &filter_error {
+                text.push(error.to_string());
+            } else if let Some(filter) = &display_filter {
+                text.push(format!("Filter: {}", filter));
+            }
+            if status.as_ref().map_or(false, |status| status.comment_pending) {
+                text.push("Comment pending".to_string());
+            }
+            if let Some(replay) = status.as_ref().and_then(|status| status.replay) {
+                text.push(if replay.finished {
+                    "Replay finished".to_string()
+                } else if replay.paused {
+                    format!("Replay {} (paused)", replay.speed)
+                } else {
+                    format!("Replay {}", replay.speed)
+                });
+            }
+            let text = text.join(" | ");
+            expect!(
+                ui =>
+                    ui.terminal.draw(|frame| {
+                        if let Some(hunt) = &mut hunt {
+                            return hunt.render(frame, frame.size(), &devices)
                         }
-                        Extension(_) => {
+                        let areas = Layout::default()
+                            .direction(Direction::Vertical)
+                            .margin(0)
+                            .constraints([Constraint::Length(2), Constraint::Length(1), Constraint::Min(0), Constraint::Length(if prompt.is_some() { 3 } else { 0 })])
+                            .split(frame.size());
+                        let top = Layout::default()
+                            .direction(Direction::Horizontal)
+                            .margin(0)
+                            .constraints([Constraint::Min(0), Constraint::Length(text.len() as u16 + 1)])
+                            .split(areas[0]);
+                        frame.render_widget(
+                            Tabs::new(tabs.titles.clone())
+                                .block(Block::default().borders(Borders::BOTTOM))
+                                .select(tabs.index)
+                                .style(Style::reset())
+                                .highlight_style(Style::reset().add_modifier(Modifier::BOLD | Modifier::REVERSED)),
+                            top[0]
+                        );
+                        frame.render_widget(
+                            Paragraph::new(text.as_str())
+                                .block(Block::default().borders(Borders::BOTTOM))
+                                .style(Style::reset().fg(Color::LightCyan))
+                                .alignment(Alignment::Right),
+                            top[1]
+                        );
+                        frame.render_widget(status_bar, areas[1]);
+                        pages[tabs.index].render(frame, areas[2], &mut devices, display_filter.as_ref());
+                        if let Some((_, prompt)) = &prompt {
+                            frame.render_widget(prompt.widget(), areas[3])
                         }
-                    }
-                }
+                    }),
+                    "Unable to draw to stdout"
+            );
+            last_draw = Some(time::Instant::now());
+            changed = false;
+        }
+
+        if hunt.as_mut().map_or(false, page::Hunt::ring) {
+            let backend = ui.terminal.backend_mut();
+            expect!(ui => backend.write_all(b"\x07").and_then(|_| backend.flush()), "Unable to ring the terminal bell");
+        }
+
+        expect!(ui => autosave(session_file, &mut last_save, || session::Session::new(&devices.lock().unwrap())), "Unable to save session");
+
+        // Sleep until the capture thread has news, checking for key presses at least once a frame
+        let first = match sniffer.updates.recv_timeout(frame_interval) {
+            Ok(update) => Some(update),
+            Err(mpsc::RecvTimeoutError::Timeout) => None,
+            // The capture thread only stops on its own after reporting an error, so it must have panicked
+            Err(mpsc::RecvTimeoutError::Disconnected) => break 'sniff
+        };
+        for update in first.into_iter().chain(sniffer.updates.try_iter()) {
+            match update {
+                sniffer::Update::Processed => changed = true,
+                sniffer::Update::Status(update) => {
+                    status = Some(update);
+                    changed = true
+                },
+                sniffer::Update::Failed(failure) => fail(&mut ui, failure)
             }
         }
     }
+    if let Err(failure) = sniffer.stop() {
+        fail(&mut ui, failure)
+    }
+    if let Some(session_file) = session_file {
+        expect!(ui => session::save(session_file, &devices.lock().unwrap()), "Unable to save session");
+    }
 }
 
-#[derive(Debug)]
-pub struct Transmission {
-    instant: time::Instant,
-    signal: Option<radiotap::field::AntennaSignal>,
-    channel: Option<radiotap::field::Channel>
-}
-impl Transmission {
-    fn new(radiotap: Radiotap) -> Self {
-        Self {
-            instant: time::Instant::now(),
-            signal: radiotap.antenna_signal,
-            channel: radiotap.channel
+/// Summarise the health of the capture in a single line
+fn status_bar(name: &str, devices: &DeviceList, status: Option<&sniffer::Status>, packet_rate: f64, uptime: time::Duration) -> Paragraph<'static> {
+    let value = Style::reset().fg(Color::LightCyan);
+    // Counts of lost or unusable frames stand out once there are any
+    let problem = |count: u64| Style::reset().fg(if count > 0 { Color::LightRed } else { Color::LightCyan });
+    let counts = &devices.counts;
+    let mut items = vec![
+        vec![Span::styled(name.to_string(), Style::reset().add_modifier(Modifier::BOLD))],
+        vec![
+            Span::raw("Channel "),
+            Span::styled(counts.channel.map_or("?".to_string(), |channel| channel.to_string()), value)
+        ],
+        vec![Span::styled(format!("{:.0}", packet_rate), value), Span::raw(" pkt/s")],
+        vec![
+            Span::raw("Mgmt "),
+            Span::styled(counts.management.to_string(), value),
+            Span::raw(" Ctrl "),
+            Span::styled(counts.control.to_string(), value),
+            Span::raw(" Data "),
+            Span::styled(counts.data.to_string(), value)
+        ],
+        vec![
+            Span::raw("Errors "),
+            Span::styled(counts.malformed.to_string(), problem(counts.malformed)),
+            Span::raw(" Bad FCS "),
+            Span::styled(counts.corrupted.to_string(), problem(counts.corrupted))
+        ]
+    ];
+    if let Some(stats) = status.and_then(|status| status.stats) {
+        let dropped = stats.dropped as u64 + stats.if_dropped as u64;
+        items.push(vec![Span::raw("Dropped "), Span::styled(dropped.to_string(), problem(dropped))]);
+    }
+    let active = devices.active(devices.now.checked_sub(ACTIVE_WINDOW).unwrap_or(devices.now));
+    items.push(vec![
+        Span::raw("Devices "),
+        Span::styled(devices.len().to_string(), value),
+        Span::raw(" ("),
+        Span::styled(active.to_string(), value),
+        Span::raw(" active, ~"),
+        Span::styled(devices.estimate_devices().to_string(), value),
+        Span::raw(" physical)")
+    ]);
+    let seconds = uptime.as_secs();
+    items.push(vec![
+        Span::raw("Up "),
+        Span::styled(format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60), value)
+    ]);
+    if let Some(size) = status.and_then(|status| status.saved_size) {
+        items.push(vec![Span::raw("Saved "), Span::styled(format!("{:.1} MB", size as f64 / 1_000_000.0), value)]);
+    }
+    let mut spans = vec![];
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" | "));
         }
+        spans.extend(item);
     }
+    Paragraph::new(Spans::from(spans)).style(Style::reset())
 }
 
-/// A device tracked by blockade
-/// Tracks metadata relating to the device
-#[derive(Debug)]
-pub struct KnownDevice {
-    manufacturer: Option<OuiEntry>,
-    /// The SSID of the beacon, or None if not a beacon
-    beacon: Option<String>,
-    /// The last transmission from this device, or None if known by reference only
-    sent: Option<Transmission>,
-    /// The devices that this one has referenced
-    knows: HashSet<MacAddress>
+/// Show an error from the capture thread and exit
+fn fail(ui: &mut ui::Ui, failure: sniffer::Failure) -> ! {
+    ui.error(failure.location, failure.message, &failure.reason);
+    panic!("{}: {}", failure.message, failure.reason)
 }
-impl KnownDevice {
-    fn new(address: MacAddress, oui_db: &OuiDatabase) -> Self {
-        Self {
-            manufacturer: oui_db.query_by_mac(&address).unwrap(/* Library should never be able to return an error */),
-            beacon: None,
-            sent: None,
-            knows: HashSet::new()
-        }
-    }
-    fn sent(&mut self, radiotap: Radiotap) -> &mut Self {
-        self.sent = Some(Transmission::new(radiotap));
-        self
+
+
+/// Sniff packets while reporting what has been found as lines of JSON
+fn headless(mut log: event::Log, mut source: capture::Source, mut devices: DeviceList, oui_db: &OuiDatabase, session_file: Option<&path::Path>, terminate: &AtomicBool) {
+    // Nobody is watching a replay, so it may as well be handled as fast as possible
+    if let capture::Source::Replay(replay) = &mut source {
+        replay.set_speed(capture::Speed::Unlimited)
     }
-    fn knows(&mut self, address: MacAddress) -> &mut Self {
-        self.knows.insert(address);
-        self
+    devices.events = Some(event::Events::new(log.clone()));
+    let mut last_save = time::Instant::now();
+    let mut last_flush = time::Instant::now();
+    while !terminate.load(Ordering::Relaxed) {
+        match expect!(log => source.next(), "Unable to get next packet") {
+            Some(packet) => if let Err(error) = devices.process_packet(&packet, oui_db) {
+                log.warning(format!("{}:{}:{}", file!(), line!(), column!()), "Unable to parse radiotap header", &error)
+            },
+            None if source.finished() => break,
+            None => thread::sleep(IDLE_INTERVAL)
+        }
+        expect!(log => source.tick(), "Unable to save to the capture file");
+
+        if let Some(error) = devices.events.as_mut().and_then(event::Events::take_error) {
+            let _: () = expect!(log => Err(error), "Unable to write events");
+        }
+        if last_flush.elapsed() >= EVENT_FLUSH_INTERVAL {
+            expect!(log => log.flush(), "Unable to write events");
+            last_flush = time::Instant::now();
+        }
+        expect!(log => autosave(session_file, &mut last_save, || session::Session::new(&devices)), "Unable to save session");
     }
-    fn beacon(&mut self, ssid: String) -> &mut Self {
-        self.beacon = Some(ssid);
-        self
+    expect!(log => source.flush(), "Unable to flush the capture file");
+    if let Some(session_file) = session_file {
+        expect!(log => session::save(session_file, &devices), "Unable to save session");
     }
-    /// Dummy function to consume the unnecessary reference
-    fn done(&mut self) {}
+    expect!(log => log.flush(), "Unable to write events");
 }
 
-#[derive(Debug, Default)]
-pub struct DeviceList(HashMap<MacAddress, KnownDevice>);
-impl DeviceList {
-    fn get_or_default(&mut self, address: MacAddress, oui_db: &OuiDatabase) -> &mut KnownDevice {
-        if self.contains_key(&address) {
-            self.get_mut(&address).unwrap()
-        } else {
-            self.insert(address, KnownDevice::new(address, oui_db));
-            self.get_mut(&address).unwrap()
-        }
+/// Save the session if one is in use and it is due to be saved
+///
+/// The snapshot is only taken when a save is due, so any lock it needs is not taken otherwise and is released before writing.
+fn autosave(session_file: Option<&path::Path>, last_save: &mut time::Instant, snapshot: impl FnOnce() -> session::Session) -> Result<(), session::Error> {
+    match session_file {
+        Some(session_file) if last_save.elapsed() >= AUTOSAVE_INTERVAL => {
+            *last_save = time::Instant::now();
+            snapshot().save(session_file)
+        },
+        _ => Ok(())
     }
-    pub fn bar_data(&self) -> Vec<(&str, u64)> {
-        let mut manufacturers = HashMap::new();
-        for device in self.values() {
-            if let Some(OuiEntry { name_short, ..}) = &device.manufacturer {
-                if let Some(count) = manufacturers.get_mut(name_short.as_str()) {
-                    *count += 1
-                } else {
-                    manufacturers.insert(name_short.as_str(), 1u64);
-                }
-            }
+}
+
+/// Where errors and findings are shown
+enum Frontend {
+    Tui(ui::Ui),
+    /// Reports findings and errors as lines of JSON
+    Headless(event::Log)
+}
+impl Frontend {
+    fn error(&mut self, location: String, message: &str, error: &dyn std::fmt::Display) {
+        match self {
+            Self::Tui(ui) => ui.error(location, message, error),
+            Self::Headless(log) => log.error(location, message, error)
         }
-        let mut values: Vec<(&str, u64)> = manufacturers.iter().map(|(&name, &count)| (name, count)).collect();
-        values.sort_by(|(nl, l), (nr, r)| l.cmp(r).then_with(|| nl.cmp(nr)));
-        values.reverse();
-        values
     }
 }
-impl Deref for DeviceList {
-    type Target = HashMap<MacAddress, KnownDevice>;
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
+
+/// What the text being typed in by the user is for
+#[derive(Debug, Clone, Copy)]
+enum Prompting {
+    Comment,
+    DisplayFilter
 }
-impl DerefMut for DeviceList {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
\ No newline at end of file
+
+/// The file name used to save captured packets when none is given, without the extension
+const DEFAULT_WRITE_TEMPLATE: &str = "capture-{interface}-{time}";
+/// The most bytes of each packet to capture
+const SNAPLEN: i32 = 65535;
+
+/// The most times per second to redraw the TUI when not given
+const DEFAULT_FRAME_RATE: u32 = 20;
+/// How long the packet rate is averaged over
+const RATE_INTERVAL: time::Duration = time::Duration::from_secs(1);
+/// How recently a device must have been heard to count as active
+const ACTIVE_WINDOW: time::Duration = time::Duration::from_secs(60);
+/// The longest time to go without redrawing, so that relative times stay current
+const REFRESH_INTERVAL: time::Duration = time::Duration::from_secs(1);
+/// How often to save the session while sniffing
+const AUTOSAVE_INTERVAL: time::Duration = time::Duration::from_secs(30);
+/// How long to wait for packets when headless and none are ready
+const IDLE_INTERVAL: time::Duration = time::Duration::from_millis(10);
+/// How often to flush events when headless
+const EVENT_FLUSH_INTERVAL: time::Duration = time::Duration::from_secs(1);
@@ -6,20 +6,33 @@ use tui::{
 };
 
 mod devices;
+mod networks;
 mod manufacturers;
+mod hunt;
 
 pub use devices::Devices;
+pub use networks::Networks;
 pub use manufacturers::Manufacturers;
+pub use hunt::Hunt;
 
-use crate::{DeviceList, ui};
+use eui48::MacAddress;
+use blockade_recon::DeviceList;
+use crate::{filter::Filter, ui};
 
 pub trait Page {
     fn name(&self) -> &'static str;
-    fn render(&mut self, frame: &mut Frame<ui::Backend>, area: Rect, devices: &mut DeviceList);
+    /// Draw the page, showing only the devices that pass the display filter
+    fn render(&mut self, frame: &mut Frame<ui::Backend>, area: Rect, devices: &mut DeviceList, filter: Option<&Filter>);
     fn up(&mut self);
     fn down(&mut self);
     fn top(&mut self);
     fn bottom(&mut self);
     fn left(&mut self);
     fn right(&mut self);
+    /// Flip the order that items are sorted in
+    fn reverse(&mut self) {}
+    /// The device that is selected, if the page lists devices
+    fn selected(&self) -> Option<MacAddress> {
+        None
+    }
 }
\ No newline at end of file
@@ -1,33 +1,148 @@
-use oui::{OuiEntry};
+use std::{cmp::Ordering, time::SystemTime};
+use eui48::MacAddress;
 use termion::{input::MouseTerminal, raw::RawTerminal, screen::AlternateScreen};
 use tui::{
     backend::TermionBackend,
     layout::{Rect, Constraint, Direction, Layout},
-    widgets::{Paragraph, Block, Borders, List, ListItem},
+    widgets::{Paragraph, Block, Borders, Cell, Row, Sparkline, Table},
     style::{Style, Modifier, Color},
     text::{Span, Spans},
     terminal::Frame
 };
 
 use super::Page;
-use crate::{DeviceList, ui};
+use blockade_recon::{wifi, AddressClass, DeviceList, KnownDevice, Role};
+use crate::{filter::{self, Filter}, ui};
+
+/// A column of the device table, which the table may be sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Mac,
+    Class,
+    Vendor,
+    Role,
+    Ssid,
+    Signal,
+    Channel,
+    Frames,
+    FirstSeen,
+    LastSeen
+}
+impl Column {
+    const ALL: [Self; 10] = [Self::Mac, Self::Class, Self::Vendor, Self::Role, Self::Ssid, Self::Signal, Self::Channel, Self::Frames, Self::FirstSeen, Self::LastSeen];
+    fn title(self) -> &'static str {
+        match self {
+            Self::Mac => "MAC",
+            Self::Class => "Address",
+            Self::Vendor => "Vendor",
+            Self::Role => "Role",
+            Self::Ssid => "SSID",
+            Self::Signal => "RSSI",
+            Self::Channel => "Ch",
+            Self::Frames => "Frames",
+            Self::FirstSeen => "First",
+            Self::LastSeen => "Last"
+        }
+    }
+    fn width(self) -> Constraint {
+        match self {
+            Self::Mac => Constraint::Length(17),
+            Self::Class => Constraint::Length(9),
+            Self::Vendor => Constraint::Length(10),
+            Self::Role => Constraint::Length(6),
+            Self::Ssid => Constraint::Min(8),
+            Self::Signal => Constraint::Length(4),
+            Self::Channel => Constraint::Length(3),
+            Self::Frames => Constraint::Length(7),
+            Self::FirstSeen | Self::LastSeen => Constraint::Length(6)
+        }
+    }
+}
+
+/// The colour of a device's role, dimmed if it is only known by reference from other devices
+fn role_style(device: &KnownDevice) -> Style {
+    let colour = match device.role {
+        Some(Role::AccessPoint) => Color::LightGreen,
+        Some(Role::Client) => Color::LightCyan,
+        Some(Role::AdHoc) => Color::LightYellow,
+        Some(Role::Wds) => Color::LightRed,
+        Some(Role::Mesh) => Color::LightMagenta,
+        None => Color::Gray
+    };
+    let style = Style::reset().fg(colour);
+    if device.sent.is_some() {
+        style
+    } else {
+        style.add_modifier(Modifier::DIM)
+    }
+}
+
+/// The network a device advertises, or the network of the BSS it is in
+fn ssid<'a>(devices: &'a DeviceList, device: &'a KnownDevice) -> Option<&'a str> {
+    device.ssid().or_else(|| device.bss.and_then(|bssid| devices.get(&bssid)).and_then(KnownDevice::ssid))
+}
+
+/// The channel an access point operates on, or else the channel a device was last heard on
+fn channel(device: &KnownDevice) -> Option<wifi::Channel> {
+    device.channel.or_else(|| device.sent.as_ref().and_then(blockade_recon::Transmission::channel))
+}
+
+/// Compare values that may be unknown, keeping unknown values last in either direction
+fn compare<T: Ord>(l: Option<T>, r: Option<T>, reversed: bool) -> Ordering {
+    match (l, r) {
+        (Some(l), Some(r)) if reversed => r.cmp(&l),
+        (Some(l), Some(r)) => l.cmp(&r),
+        (l, r) => r.is_some().cmp(&l.is_some())
+    }
+}
 
 pub struct Devices {
-    device_state: ui::ListState
+    device_state: ui::TableState,
+    /// The addresses in the order they were last shown, used to keep the same device selected as the order changes
+    order: Vec<MacAddress>,
+    sort: Column,
+    reversed: bool
 }
 impl Devices {
     pub fn new() -> Self {
         Self {
-            device_state: Default::default()
+            device_state: Default::default(),
+            order: vec![],
+            sort: Column::Mac,
+            reversed: false
         }
     }
+    /// Sort the visible devices by the chosen column, breaking ties by address so that the order is stable
+    fn sorted(&self, devices: &DeviceList, filter: Option<&Filter>) -> Vec<MacAddress> {
+        let mut visible: Vec<_> = filter::visible(filter, devices).collect();
+        visible.sort_by(|(l_mac, l), (r_mac, r)| {
+            let reversed = self.reversed;
+            match self.sort {
+                Column::Mac => Ordering::Equal,
+                Column::Class => compare(Some(AddressClass::of(l_mac)), Some(AddressClass::of(r_mac)), reversed),
+                Column::Vendor => compare(
+                    l.manufacturer.as_ref().map(|m| m.name_short.to_lowercase()),
+                    r.manufacturer.as_ref().map(|m| m.name_short.to_lowercase()),
+                    reversed
+                ),
+                Column::Role => compare(l.role, r.role, reversed),
+                Column::Ssid => compare(ssid(devices, l).map(str::to_lowercase), ssid(devices, r).map(str::to_lowercase), reversed),
+                Column::Signal => compare(l.sent.as_ref().and_then(|sent| sent.signal), r.sent.as_ref().and_then(|sent| sent.signal), reversed),
+                Column::Channel => compare(channel(l), channel(r), reversed),
+                Column::Frames => compare(Some(l.frames), Some(r.frames), reversed),
+                Column::FirstSeen => compare(l.first_sent, r.first_sent, reversed),
+                Column::LastSeen => compare(l.sent.as_ref().map(|sent| sent.time), r.sent.as_ref().map(|sent| sent.time), reversed)
+            }.then_with(|| if reversed && self.sort == Column::Mac { r_mac.cmp(l_mac) } else { l_mac.cmp(r_mac) })
+        });
+        visible.into_iter().map(|(&mac, _)| mac).collect()
+    }
 }
 impl Page for Devices {
     fn name(&self) -> &'static str {
         "Devices"
     }
 
-    fn render(&mut self, frame: &mut Frame<TermionBackend<AlternateScreen<MouseTerminal<RawTerminal<std::io::Stdout>>>>>, area: Rect, devices: &mut DeviceList) {
+    fn render(&mut self, frame: &mut Frame<TermionBackend<AlternateScreen<MouseTerminal<RawTerminal<std::io::Stdout>>>>>, area: Rect, devices: &mut DeviceList, filter: Option<&Filter>) {
         const VALUE_COLOR: Color = Color::LightCyan;
         fn format_string(value: &str) -> Span {
             Span::styled(format!("{:?}", value), Style::reset().fg(VALUE_COLOR))
@@ -36,61 +151,87 @@ impl Page for Devices {
             Spans::from(vec![Span::styled(title, Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD))])
         }
         
-        self.device_state.set_item_count(devices.len());
-        let device_list = List::new(
-            devices.iter().map(|(mac, device)| {
-                let mut spans = vec![];
-                let colour = if device.sent.is_some() {
-                    Color::LightGreen
-                } else {
-                    Color::LightYellow
-                };
-                spans.push(Span::styled(mac.to_hex_string(), Style::reset().fg(colour)));
-                if let Some(OuiEntry { name_short, name_long, ..}) = &device.manufacturer {
-                    spans.push(Span::styled(format!(" | {:8} ", name_short), Style::reset()));
-                    if let Some(name_long) = name_long {
-                        spans.push(Span::styled(format!("{}", name_long), Style::reset().fg(Color::LightCyan)));
-                    }
-                }
-                ListItem::new(vec![
-                    Spans::from(spans)
-                ])
+        // Keep the same device selected as the order changes underneath it
+        let selected = self.device_state.selected().and_then(|index| self.order.get(index).copied());
+        self.order = self.sorted(devices, filter);
+        self.device_state.set_item_count(self.order.len());
+        if let Some(index) = selected.and_then(|selected| self.order.iter().position(|&mac| mac == selected)) {
+            self.device_state.select(Some(index))
+        }
+
+        let ago = |time: SystemTime| format!("{:.0}s", devices.now.duration_since(time).unwrap_or_default().as_secs_f32());
+        let header = Row::new(Column::ALL.iter().map(|&column| {
+            if column == self.sort {
+                let arrow = if self.reversed { '▼' } else { '▲' };
+                Cell::from(format!("{}{}", column.title(), arrow)).style(Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD | Modifier::UNDERLINED))
+            } else {
+                Cell::from(column.title()).style(Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD))
             }
-            ).collect::<Vec<_>>()
-        )
+        }));
+        let rows = self.order.iter().filter_map(|mac| devices.get(mac).map(|device| (mac, device))).map(|(mac, device)| {
+            let style = role_style(device);
+            let signal = device.sent.as_ref().and_then(|sent| sent.signal);
+            Row::new(vec![
+                Cell::from(mac.to_hex_string()).style(style),
+                Cell::from(AddressClass::of(mac).to_string()),
+                Cell::from(device.manufacturer.as_ref().map_or("", |m| m.name_short.as_str())),
+                Cell::from(device.role.map_or(String::new(), |role| role.to_string())).style(style),
+                Cell::from(ssid(devices, device).unwrap_or("")),
+                Cell::from(signal.map_or(String::new(), |signal| signal.to_string())).style(Style::reset().fg(VALUE_COLOR)),
+                Cell::from(channel(device).map_or(String::new(), |channel| channel.to_string())).style(Style::reset().fg(VALUE_COLOR)),
+                Cell::from(device.frames.to_string()),
+                Cell::from(device.first_sent.map_or(String::new(), ago)),
+                Cell::from(device.sent.as_ref().map_or(String::new(), |sent| ago(sent.time)))
+            ])
+        }).collect::<Vec<_>>();
+        let widths: Vec<_> = Column::ALL.iter().map(|column| column.width()).collect();
+        let device_list = Table::new(rows)
+            .header(header)
+            .widths(&widths)
+            .column_spacing(1)
             .block(Block::default().borders(Borders::ALL).title("Devices"))
             .highlight_style(Style::default().bg(Color::Reset).add_modifier(Modifier::REVERSED))
             .highlight_symbol("> ");
-        
-        if let Some((device_mac, device)) = devices.iter().nth(self.device_state.selected().unwrap()) {
+
+        let selected = self.device_state.selected().and_then(|index| self.order.get(index)).and_then(|mac| devices.get(mac).map(|device| (mac, device)));
+        if let Some((device_mac, device)) = selected {
             let areas = Layout::default()
                 .direction(Direction::Horizontal)
                 .margin(0)
-                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
                 .split(area);
             let mut device_info = vec![];
 
-            if let Some(crate::Transmission { instant, signal, channel }) = device.sent {
+            if let Some(blockade_recon::Transmission { time, signal, frequency, rate }) = device.sent {
                 device_info.push(Spans::from(vec![
                     Span::styled("Last seen ", Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD)),
-                    Span::styled(format!("{:.1}", std::time::Instant::now().duration_since(instant).as_secs_f32()), Style::default().fg(VALUE_COLOR).add_modifier(Modifier::BOLD)),
+                    Span::styled(format!("{:.1}", devices.now.duration_since(time).unwrap_or_default().as_secs_f32()), Style::default().fg(VALUE_COLOR).add_modifier(Modifier::BOLD)),
                     Span::styled("s ago", Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD))
                 ]));
-                if signal.is_some() || channel.is_some() {
+                if signal.is_some() || frequency.is_some() || rate.is_some() {
                     device_info.push(format_header("Radio"));
                 }
                 if let Some(signal) = signal {
                     device_info.push(Spans::from(vec![
                         Span::raw("  Signal Strength: "),
-                        Span::styled(format!("{}", signal.value), Style::default().fg(VALUE_COLOR)),
+                        Span::styled(format!("{}", signal), Style::default().fg(VALUE_COLOR)),
                         Span::raw("db")
                     ]));
                 }
-                if let Some(channel) = channel {
+                if let Some(frequency) = frequency {
+                    let mut spans = vec![Span::raw("  Heard On: ")];
+                    if let Some(channel) = blockade_recon::wifi::Channel::from_frequency(frequency) {
+                        spans.push(Span::styled(format!("Channel {} ", channel), Style::default().fg(VALUE_COLOR)));
+                    }
+                    spans.push(Span::styled(format!("{}", frequency), Style::default().fg(VALUE_COLOR)));
+                    spans.push(Span::raw("MHz"));
+                    device_info.push(Spans::from(spans));
+                }
+                if let Some(rate) = rate {
                     device_info.push(Spans::from(vec![
-                        Span::raw("  Channel: "),
-                        Span::styled(format!("{}", channel.freq), Style::default().fg(VALUE_COLOR)),
-                        Span::raw("MHz")
+                        Span::raw("  Rate: "),
+                        Span::styled(format!("{}", rate), Style::default().fg(VALUE_COLOR)),
+                        Span::raw("Mbps")
                     ]));
                 }
             } else {
@@ -98,13 +239,141 @@ impl Page for Devices {
                     Span::styled("Known by reference from other devices only", Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD))
                 ]));
             }
+            if let Some(role) = device.role {
+                device_info.push(Spans::from(vec![
+                    Span::raw("Role: "),
+                    Span::styled(role.to_string(), role_style(device))
+                ]));
+            }
             if let Some(ssid) = &device.beacon {
                 device_info.push(format_header("Beacon"));
+                if device.hidden {
+                    device_info.push(Spans::from(vec![
+                        Span::raw("  SSID: "),
+                        Span::styled("Hidden", Style::reset().fg(Color::LightYellow))
+                    ]));
+                    if let Some(blockade_recon::Uncloaked { ssid, method, time }) = &device.uncloaked {
+                        device_info.push(Spans::from(vec![
+                            Span::raw("  Uncloaked: "),
+                            format_string(ssid),
+                            Span::raw(format!(" from a {} ", method)),
+                            Span::styled(format!("{:.1}", devices.now.duration_since(*time).unwrap_or_default().as_secs_f32()), Style::default().fg(VALUE_COLOR)),
+                            Span::raw("s ago")
+                        ]));
+                    }
+                } else {
+                    device_info.push(Spans::from(vec![
+                        Span::raw("  SSID: "),
+                        format_string(ssid)
+                    ]));
+                }
+                if let Some(channel) = device.channel {
+                    device_info.push(Spans::from(vec![
+                        Span::raw("  Operating Channel: "),
+                        Span::styled(channel.to_string(), Style::default().fg(VALUE_COLOR))
+                    ]));
+                }
+            }
+            if let Some(phy) = &device.phy {
+                device_info.push(format_header("Capabilities"));
+                device_info.push(Spans::from(vec![
+                    Span::raw("  Generation: "),
+                    Span::styled(phy.generation.to_string(), Style::default().fg(VALUE_COLOR))
+                ]));
+                device_info.push(Spans::from(vec![
+                    Span::raw("  Spatial Streams: "),
+                    Span::styled(phy.spatial_streams.to_string(), Style::default().fg(VALUE_COLOR))
+                ]));
+                device_info.push(Spans::from(vec![
+                    Span::raw("  Channel Width: "),
+                    Span::styled(phy.width.to_string(), Style::default().fg(VALUE_COLOR))
+                ]));
+            }
+            if let Some(security) = &device.security {
+                device_info.push(format_header("Security"));
                 device_info.push(Spans::from(vec![
-                    Span::raw("  SSID: "),
-                    format_string(ssid)
+                    Span::raw("  Summary: "),
+                    Span::styled(security.summary.to_string(), Style::default().fg(VALUE_COLOR))
                 ]));
-                
+                if let Some(rsn) = &security.rsn {
+                    let list = |items: Vec<String>| Span::styled(items.join(", "), Style::default().fg(VALUE_COLOR));
+                    device_info.push(Spans::from(vec![
+                        Span::raw("  Group Cipher: "),
+                        Span::styled(rsn.group_cipher.to_string(), Style::default().fg(VALUE_COLOR))
+                    ]));
+                    device_info.push(Spans::from(vec![
+                        Span::raw("  Pairwise Ciphers: "),
+                        list(rsn.pairwise_ciphers.iter().map(|c| c.to_string()).collect())
+                    ]));
+                    device_info.push(Spans::from(vec![
+                        Span::raw("  Key Management: "),
+                        list(rsn.akm_suites.iter().map(|a| a.to_string()).collect())
+                    ]));
+                    let pmf = if rsn.mfp_required() {
+                        "Required"
+                    } else if rsn.mfp_capable() {
+                        "Capable"
+                    } else {
+                        "Disabled"
+                    };
+                    device_info.push(Spans::from(vec![
+                        Span::raw("  Protected Management Frames: "),
+                        Span::styled(pmf, Style::default().fg(VALUE_COLOR))
+                    ]));
+                }
+            }
+            if !device.probes.is_empty() {
+                device_info.push(format_header("Probes"));
+                let mut probes: Vec<_> = device.probes.iter().collect();
+                probes.sort_by(|(_, l), (_, r)| r.last_seen.cmp(&l.last_seen));
+                let ago = |time| devices.now.duration_since(time).unwrap_or_default().as_secs_f32();
+                for (ssid, probe) in probes {
+                    device_info.push(Spans::from(vec![
+                        Span::raw("  "),
+                        format_string(ssid),
+                        Span::raw(" first "),
+                        Span::styled(format!("{:.1}", ago(probe.first_seen)), Style::default().fg(VALUE_COLOR)),
+                        Span::raw("s ago, last "),
+                        Span::styled(format!("{:.1}", ago(probe.last_seen)), Style::default().fg(VALUE_COLOR)),
+                        Span::raw("s ago")
+                    ]));
+                }
+            }
+            let associations = devices.associations(*device_mac);
+            if !associations.is_empty() {
+                device_info.push(format_header("Associations"));
+                for (peer, association) in associations {
+                    let mut spans = vec![
+                        Span::styled(format!("  {} ", peer.to_hex_string()), Style::reset().fg(Color::LightCyan)),
+                        Span::raw(association.state.to_string())
+                    ];
+                    if let Some(aid) = association.aid {
+                        spans.push(Span::raw(format!(", AID {}", aid)));
+                    }
+                    device_info.push(Spans::from(spans));
+                    if let Some((time, event)) = association.history.back() {
+                        device_info.push(Spans::from(vec![
+                            Span::raw(format!("    {} ", event)),
+                            Span::styled(format!("{:.1}", devices.now.duration_since(*time).unwrap_or_default().as_secs_f32()), Style::default().fg(VALUE_COLOR)),
+                            Span::raw("s ago")
+                        ]));
+                    }
+                }
+            }
+            if let Some(fingerprint) = device.fingerprint {
+                device_info.push(format_header("Fingerprint"));
+                device_info.push(Spans::from(vec![
+                    Span::raw("  Probe Fingerprint: "),
+                    Span::styled(fingerprint.to_string(), Style::default().fg(VALUE_COLOR))
+                ]));
+                if let Some(physical) = devices.physical_device(device_mac).filter(|physical| physical.addresses.len() > 1) {
+                    device_info.push(Spans::from(vec![Span::raw("  Same device as:")]));
+                    for address in physical.addresses.iter().filter(|&address| address != device_mac) {
+                        device_info.push(Spans::from(vec![
+                            Span::styled(format!("    {}", address.to_hex_string()), Style::reset().fg(Color::LightCyan))
+                        ]))
+                    }
+                }
             }
             if let Some(manufacturer) = &device.manufacturer {
                 device_info.push(format_header("Manufacturer"));
@@ -140,7 +409,31 @@ impl Page for Devices {
             let device_info = Paragraph::new(device_info)
                 .block(Block::default().borders(Borders::ALL).title(device_mac.to_hex_string()));
             frame.render_stateful_widget(device_list, areas[0], &mut self.device_state);
-            frame.render_widget(device_info, areas[1])
+
+            // Chart the signal over the remembered transmissions, to show whether the device is getting closer
+            let signals: Vec<i8> = device.signals().collect();
+            if let (Some(&min), Some(&max)) = (signals.iter().min(), signals.iter().max()) {
+                let average = signals.iter().map(|&signal| signal as f32).sum::<f32>() / signals.len() as f32;
+                let info_areas = Layout::default()
+                    .direction(Direction::Vertical)
+                    .margin(0)
+                    .constraints([Constraint::Length(6), Constraint::Min(0)])
+                    .split(areas[1]);
+                // Only the newest transmissions fit, measured from just below the weakest signal so that each shows as a bar
+                let width = info_areas[0].width.saturating_sub(2) as usize;
+                let data: Vec<u64> = signals.iter()
+                    .skip(signals.len().saturating_sub(width))
+                    .map(|&signal| (signal as i16 - min as i16 + 1) as u64)
+                    .collect();
+                let sparkline = Sparkline::default()
+                    .block(Block::default().borders(Borders::ALL).title(format!("Signal min {} avg {:.0} max {} dBm", min, average, max)))
+                    .style(Style::default().fg(Color::LightGreen))
+                    .data(&data);
+                frame.render_widget(sparkline, info_areas[0]);
+                frame.render_widget(device_info, info_areas[1])
+            } else {
+                frame.render_widget(device_info, areas[1])
+            }
         } else {
             frame.render_stateful_widget(device_list, area, &mut self.device_state);
         }
@@ -160,9 +453,17 @@ impl Page for Devices {
         self.device_state.bottom()
     }
     fn left(&mut self) {
-        
+        let index = Column::ALL.iter().position(|&column| column == self.sort).unwrap_or(0);
+        self.sort = Column::ALL[(index + Column::ALL.len() - 1) % Column::ALL.len()]
     }
     fn right(&mut self) {
-        
+        let index = Column::ALL.iter().position(|&column| column == self.sort).unwrap_or(0);
+        self.sort = Column::ALL[(index + 1) % Column::ALL.len()]
+    }
+    fn reverse(&mut self) {
+        self.reversed = !self.reversed
+    }
+    fn selected(&self) -> Option<MacAddress> {
+        self.device_state.selected().and_then(|index| self.order.get(index).copied())
     }
 }
\ No newline at end of file
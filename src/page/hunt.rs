@@ -0,0 +1,183 @@
+use std::{collections::VecDeque, time::{Duration, Instant, SystemTime}};
+use eui48::MacAddress;
+use tui::{
+    layout::{Alignment, Rect, Constraint, Direction, Layout},
+    widgets::{Paragraph, Block, Borders, Gauge},
+    style::{Style, Modifier, Color},
+    text::{Span, Spans},
+    terminal::Frame
+};
+
+use blockade_recon::DeviceList;
+use crate::ui;
+
+/// How much each new transmission moves the smoothed signal, between 0 and 1
+const SMOOTHING: f32 = 0.3;
+/// How far back the smoothed signal is compared against to find the rate of change
+const TREND_WINDOW: Duration = Duration::from_secs(5);
+/// The change in signal per second below which the device is considered to be staying put
+const STEADY: f32 = 0.5;
+/// How long the device may go unheard before it is considered lost and the bell stops
+const LOST_AFTER: Duration = Duration::from_secs(5);
+/// The signals shown as an empty and a full gauge, in dBm
+const SIGNAL_FLOOR: f32 = -100.0;
+const SIGNAL_CEILING: f32 = -20.0;
+/// The time between bells at the weakest and strongest signals
+const BELL_SLOWEST: Duration = Duration::from_millis(2000);
+const BELL_FASTEST: Duration = Duration::from_millis(100);
+
+/// A full screen view for physically tracking down a single transmitter by its signal strength
+pub struct Hunt {
+    pub target: MacAddress,
+    /// The signal with the noise of individual frames smoothed out
+    smoothed: Option<f32>,
+    /// The time of the last transmission fed into the smoothed signal
+    heard: Option<SystemTime>,
+    /// The smoothed signal over the trend window, oldest first
+    samples: VecDeque<(SystemTime, f32)>,
+    /// Whether to ring the terminal bell faster as the signal gets stronger
+    pub bell: bool,
+    /// How often the bell should ring, or None if it should be quiet
+    cadence: Option<Duration>,
+    last_bell: Option<Instant>
+}
+impl Hunt {
+    pub fn new(target: MacAddress) -> Self {
+        Self {
+            target,
+            smoothed: None,
+            heard: None,
+            samples: VecDeque::new(),
+            bell: false,
+            cadence: None,
+            last_bell: None
+        }
+    }
+    pub fn toggle_bell(&mut self) {
+        self.bell = !self.bell
+    }
+    /// Whether the bell is due to ring, in which case it is assumed to be rung
+    pub fn ring(&mut self) -> bool {
+        match self.cadence {
+            Some(cadence) if self.last_bell.map_or(true, |last| last.elapsed() >= cadence) => {
+                self.last_bell = Some(Instant::now());
+                true
+            },
+            _ => false
+        }
+    }
+    /// Feed the transmissions heard since the last update into the smoothed signal
+    fn update(&mut self, devices: &DeviceList) {
+        let device = match devices.get(&self.target) {
+            Some(device) => device,
+            None => return
+        };
+        for transmission in &device.history {
+            if self.heard.map_or(false, |heard| transmission.time <= heard) {
+                continue
+            }
+            self.heard = Some(transmission.time);
+            if let Some(signal) = transmission.signal {
+                let smoothed = match self.smoothed {
+                    Some(smoothed) => smoothed + SMOOTHING * (signal as f32 - smoothed),
+                    None => signal as f32
+                };
+                self.smoothed = Some(smoothed);
+                self.samples.push_back((transmission.time, smoothed));
+            }
+        }
+        while let (Some(&(oldest, _)), Some(&(newest, _))) = (self.samples.front(), self.samples.back()) {
+            if newest.duration_since(oldest).unwrap_or_default() <= TREND_WINDOW {
+                break
+            }
+            self.samples.pop_front();
+        }
+    }
+    /// The change in the smoothed signal in dB per second, once there is enough history to tell
+    fn trend(&self) -> Option<f32> {
+        let (&(oldest, from), &(newest, to)) = (self.samples.front()?, self.samples.back()?);
+        let elapsed = newest.duration_since(oldest).ok()?.as_secs_f32();
+        if elapsed < 1.0 {
+            return None
+        }
+        Some((to - from) / elapsed)
+    }
+
+    pub fn render(&mut self, frame: &mut Frame<ui::Backend>, area: Rect, devices: &DeviceList) {
+        self.update(devices);
+        let device = devices.get(&self.target);
+        let since = device
+            .and_then(|device| device.sent.as_ref())
+            .map(|sent| devices.now.duration_since(sent.time).unwrap_or_default());
+        let lost = since.map_or(true, |since| since > LOST_AFTER);
+        let strength = self.smoothed.map(|signal| ((signal - SIGNAL_FLOOR) / (SIGNAL_CEILING - SIGNAL_FLOOR)).max(0.0).min(1.0));
+        self.cadence = match strength {
+            Some(strength) if self.bell && !lost => Some(BELL_SLOWEST - (BELL_SLOWEST - BELL_FASTEST).mul_f32(strength)),
+            _ => None
+        };
+
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(0)
+            .constraints([Constraint::Length(2), Constraint::Min(3), Constraint::Length(5)])
+            .split(area);
+        let mut title = vec![Span::styled(self.target.to_hex_string(), Style::reset().fg(Color::Blue).add_modifier(Modifier::BOLD))];
+        if let Some(name) = device.and_then(|device| device.manufacturer.as_ref()).map(|manufacturer| &manufacturer.name_short) {
+            title.push(Span::raw(format!(" {}", name)));
+        }
+        if let Some(ssid) = device.and_then(|device| device.ssid()) {
+            title.push(Span::styled(format!(" {:?}", ssid), Style::reset().fg(Color::LightGreen)));
+        }
+        title.push(Span::styled("  (b: bell, Esc: back)", Style::reset().fg(Color::DarkGray)));
+        frame.render_widget(
+            Paragraph::new(Spans::from(title)).block(Block::default().borders(Borders::BOTTOM)),
+            areas[0]
+        );
+
+        let colour = match strength {
+            _ if lost => Color::DarkGray,
+            Some(strength) if strength >= 0.6 => Color::LightGreen,
+            Some(strength) if strength >= 0.3 => Color::LightYellow,
+            _ => Color::LightRed
+        };
+        let label = match self.smoothed {
+            Some(signal) => format!("{:.0} dBm", signal),
+            None => "No signal heard".to_string()
+        };
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::default().borders(Borders::ALL).title("Signal"))
+                .gauge_style(Style::reset().fg(colour))
+                .ratio(strength.unwrap_or(0.0) as f64)
+                .label(Span::styled(label, Style::reset().add_modifier(Modifier::BOLD))),
+            areas[1]
+        );
+
+        let panels = Layout::default()
+            .direction(Direction::Horizontal)
+            .margin(0)
+            .constraints([Constraint::Ratio(1, 3), Constraint::Ratio(1, 3), Constraint::Ratio(1, 3)])
+            .split(areas[2]);
+        let panel = |title: &'static str, text: String, style: Style| Paragraph::new(vec![Spans::default(), Spans::from(Span::styled(text, style))])
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .alignment(Alignment::Center);
+        let (trend, style) = match self.trend() {
+            Some(trend) if trend >= STEADY => (format!("▲ Closer {:+.1} dB/s", trend), Style::reset().fg(Color::LightGreen)),
+            Some(trend) if trend <= -STEADY => (format!("▼ Further {:+.1} dB/s", trend), Style::reset().fg(Color::LightRed)),
+            Some(trend) => (format!("● Steady {:+.1} dB/s", trend), Style::reset().fg(Color::LightCyan)),
+            None => ("Listening...".to_string(), Style::reset().fg(Color::DarkGray))
+        };
+        frame.render_widget(panel("Trend", trend, style.add_modifier(Modifier::BOLD)), panels[0]);
+        let (heard, style) = match since {
+            Some(since) => (format!("{:.1}s ago", since.as_secs_f32()), Style::reset().fg(if lost { Color::LightRed } else { Color::LightCyan })),
+            None => ("Never".to_string(), Style::reset().fg(Color::LightRed))
+        };
+        frame.render_widget(panel("Last Heard", heard, style.add_modifier(Modifier::BOLD)), panels[1]);
+        let bell = match self.cadence {
+            Some(cadence) => format!("Every {:.1}s", cadence.as_secs_f32()),
+            None if self.bell => "Quiet until heard".to_string(),
+            None => "Off".to_string()
+        };
+        frame.render_widget(panel("Bell", bell, Style::reset().fg(Color::LightCyan)), panels[2]);
+    }
+}
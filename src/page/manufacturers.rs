@@ -1,14 +1,19 @@
 use termion::{input::MouseTerminal, raw::RawTerminal, screen::AlternateScreen};
 use tui::{
     backend::TermionBackend,
-    layout::Rect,
+    layout::{Rect, Constraint, Direction, Layout},
     widgets::{BarChart, Block, Borders},
     style::{Style, Modifier, Color},
     terminal::Frame
 };
 
+use std::collections::HashMap;
+use eui48::MacAddress;
+use oui::OuiEntry;
+
 use super::Page;
-use crate::DeviceList;
+use blockade_recon::{wifi, AddressClass, DeviceList, KnownDevice};
+use crate::filter::{self, Filter};
 
 pub struct Manufacturers {
 
@@ -25,8 +30,17 @@ impl Page for Manufacturers {
         "Manufacturers"
     }
 
-    fn render(&mut self, frame: &mut Frame<TermionBackend<AlternateScreen<MouseTerminal<RawTerminal<std::io::Stdout>>>>>, area: Rect, devices: &mut DeviceList) {
-        let bar_data = devices.bar_data();
+    fn render(&mut self, frame: &mut Frame<TermionBackend<AlternateScreen<MouseTerminal<RawTerminal<std::io::Stdout>>>>>, area: Rect, devices: &mut DeviceList, filter: Option<&Filter>) {
+        let visible: Vec<_> = filter::visible(filter, devices).collect();
+        let generation_data = generation_bar_data(&visible);
+        let areas = Layout::default()
+            .direction(Direction::Horizontal)
+            .margin(0)
+            // Fit every generation side by side
+            .constraints([Constraint::Min(0), Constraint::Length(generation_data.len() as u16 * 9 + 1)])
+            .split(area);
+
+        let bar_data = bar_data(&visible);
         let barchart = BarChart::default()
             .block(Block::default().borders(Borders::ALL).title("Manufacturers"))
             .data(&bar_data)
@@ -34,8 +48,16 @@ impl Page for Manufacturers {
             .bar_gap(1)
             .bar_style(Style::reset().fg(Color::Blue))
             .value_style(Style::reset().fg(Color::Blue).add_modifier(Modifier::REVERSED));
+        let generations = BarChart::default()
+            .block(Block::default().borders(Borders::ALL).title("Generations"))
+            .data(&generation_data)
+            .bar_width(8)
+            .bar_gap(1)
+            .bar_style(Style::reset().fg(Color::Magenta))
+            .value_style(Style::reset().fg(Color::Magenta).add_modifier(Modifier::REVERSED));
 
-        frame.render_widget(barchart, area);
+        frame.render_widget(barchart, areas[0]);
+        frame.render_widget(generations, areas[1]);
     }
 
     fn up(&mut self) {
@@ -56,4 +78,30 @@ impl Page for Manufacturers {
     fn right(&mut self) {
         
     }
-}
\ No newline at end of file
+}
+/// Count the devices of each Wi-Fi generation, oldest first
+fn generation_bar_data(devices: &[(&MacAddress, &KnownDevice)]) -> Vec<(&'static str, u64)> {
+    wifi::Generation::ALL.iter().map(|&generation| {
+        let count = devices.iter().filter(|(_, device)| device.phy.as_ref().map(|phy| phy.generation) == Some(generation)).count();
+        (generation.name(), count as u64)
+    }).collect()
+}
+fn bar_data<'a>(devices: &[(&MacAddress, &'a KnownDevice)]) -> Vec<(&'a str, u64)> {
+    let mut manufacturers = HashMap::new();
+    for &(address, device) in devices {
+        // Randomised addresses say nothing about who made the device, so are counted together
+        if AddressClass::of(address) == AddressClass::Local {
+            *manufacturers.entry("Private").or_insert(0u64) += 1
+        } else if let Some(OuiEntry { name_short, ..}) = &device.manufacturer {
+            if let Some(count) = manufacturers.get_mut(name_short.as_str()) {
+                *count += 1
+            } else {
+                manufacturers.insert(name_short.as_str(), 1u64);
+            }
+        }
+    }
+    let mut values: Vec<(&str, u64)> = manufacturers.iter().map(|(&name, &count)| (name, count)).collect();
+    values.sort_by(|(nl, l), (nr, r)| l.cmp(r).then_with(|| nl.cmp(nr)));
+    values.reverse();
+    values
+}
@@ -0,0 +1,144 @@
+use oui::OuiEntry;
+use termion::{input::MouseTerminal, raw::RawTerminal, screen::AlternateScreen};
+use tui::{
+    backend::TermionBackend,
+    layout::{Rect, Constraint, Direction, Layout},
+    widgets::{Paragraph, Block, Borders, List, ListItem},
+    style::{Style, Modifier, Color},
+    text::{Span, Spans},
+    terminal::Frame
+};
+
+use super::Page;
+use blockade_recon::{DeviceList, KnownDevice};
+use crate::{filter::Filter, ui};
+
+const VALUE_COLOR: Color = Color::LightCyan;
+
+pub struct Networks {
+    network_state: ui::ListState
+}
+impl Networks {
+    pub fn new() -> Self {
+        Self {
+            network_state: Default::default()
+        }
+    }
+}
+
+/// The short manufacturer name, if known
+fn vendor(device: &KnownDevice) -> Span<'_> {
+    match &device.manufacturer {
+        Some(OuiEntry { name_short, .. }) => Span::raw(format!(" {}", name_short)),
+        None => Span::raw("")
+    }
+}
+
+/// The last signal strength heard from a device
+fn signal(device: &KnownDevice) -> Span<'_> {
+    match device.sent.as_ref().and_then(|sent| sent.signal) {
+        Some(signal) => Span::styled(format!(" {}db", signal), Style::default().fg(VALUE_COLOR)),
+        None => Span::raw("")
+    }
+}
+
+impl Page for Networks {
+    fn name(&self) -> &'static str {
+        "Networks"
+    }
+
+    fn render(&mut self, frame: &mut Frame<TermionBackend<AlternateScreen<MouseTerminal<RawTerminal<std::io::Stdout>>>>>, area: Rect, devices: &mut DeviceList, filter: Option<&Filter>) {
+        let mut networks = devices.networks();
+        for network in &mut networks {
+            network.bssids.retain(|(bssid, device)| filter.map_or(true, |filter| filter.matches(bssid, device)))
+        }
+        networks.retain(|network| !network.bssids.is_empty());
+        self.network_state.set_item_count(networks.len());
+        let network_list = List::new(
+            networks.iter().map(|network| {
+                let clients: usize = network.bssids.iter().map(|&(bssid, _)| devices.clients(bssid).len()).sum();
+                let name = match network.ssid {
+                    Some(ssid) => Span::styled(format!("{:?}", ssid), Style::reset().fg(Color::LightGreen)),
+                    None => Span::styled("Hidden", Style::reset().fg(Color::LightYellow))
+                };
+                ListItem::new(vec![
+                    Spans::from(vec![
+                        name,
+                        Span::raw(format!(" | {} BSSIDs, {} clients", network.bssids.len(), clients))
+                    ])
+                ])
+            }).collect::<Vec<_>>()
+        )
+            .block(Block::default().borders(Borders::ALL).title("Networks"))
+            .highlight_style(Style::default().bg(Color::Reset).add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+
+        if let Some(network) = networks.get(self.network_state.selected().unwrap()) {
+            let areas = Layout::default()
+                .direction(Direction::Horizontal)
+                .margin(0)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(area);
+            let mut network_info = vec![];
+            for &(bssid, access_point) in &network.bssids {
+                network_info.push(Spans::from(vec![
+                    Span::styled(bssid.to_hex_string(), Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)),
+                    vendor(access_point),
+                    signal(access_point)
+                ]));
+                if let Some(channel) = access_point.channel {
+                    network_info.push(Spans::from(vec![
+                        Span::raw("  Channel: "),
+                        Span::styled(channel.to_string(), Style::default().fg(VALUE_COLOR))
+                    ]));
+                }
+                if let Some(security) = &access_point.security {
+                    network_info.push(Spans::from(vec![
+                        Span::raw("  Security: "),
+                        Span::styled(security.summary.to_string(), Style::default().fg(VALUE_COLOR))
+                    ]));
+                }
+                let clients = devices.clients(bssid);
+                if clients.is_empty() {
+                    network_info.push(Spans::from(vec![Span::styled("  No clients seen", Style::default().fg(Color::LightYellow))]));
+                } else {
+                    network_info.push(Spans::from(vec![Span::raw("  Clients:")]));
+                    for (address, client) in clients {
+                        network_info.push(Spans::from(vec![
+                            Span::styled(format!("    {}", address.to_hex_string()), Style::reset().fg(VALUE_COLOR)),
+                            vendor(client),
+                            signal(client)
+                        ]));
+                    }
+                }
+            }
+
+            let title = network.ssid.map_or("Hidden".to_string(), |ssid| format!("{:?}", ssid));
+            let network_info = Paragraph::new(network_info)
+                .block(Block::default().borders(Borders::ALL).title(title));
+            frame.render_stateful_widget(network_list, areas[0], &mut self.network_state);
+            frame.render_widget(network_info, areas[1])
+        } else {
+            frame.render_stateful_widget(network_list, area, &mut self.network_state);
+        }
+    }
+
+    fn up(&mut self) {
+        self.network_state.up()
+    }
+    fn top(&mut self) {
+        self.network_state.top()
+    }
+    fn down(&mut self) {
+        self.network_state.down()
+    }
+    fn bottom(&mut self) {
+        self.network_state.bottom()
+    }
+    fn left(&mut self) {
+
+    }
+    fn right(&mut self) {
+
+    }
+}
@@ -0,0 +1,185 @@
+use std::{collections::{HashMap, VecDeque}, fmt, fs, io, path::Path, time::SystemTime};
+use eui48::MacAddress;
+use oui::OuiDatabase;
+use serde::{Deserialize, Serialize};
+
+use crate::{wifi::{Channel, Fingerprint, PhyProfile, SecurityProfile}, AddressClass, Association, DeviceList, KnownDevice, PhysicalDevice, Probe, Role, Transmission, Uncloaked};
+
+/// The version of the session format written by this build
+pub const VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Format(serde_json::Error),
+    /// The session was written by a newer version of blockade
+    UnsupportedVersion(u32)
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Format(error) => write!(f, "Malformed session file: {}", error),
+            Self::UnsupportedVersion(version) => write!(f, "Session format version {} is newer than the supported version {}", version, VERSION)
+        }
+    }
+}
+impl std::error::Error for Error {}
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Format(error)
+    }
+}
+
+/// Only the version is read at first so that the rest of the file is not misinterpreted
+#[derive(Deserialize)]
+struct Header {
+    version: u32
+}
+
+/// The devices seen across runs, stored as JSON
+///
+/// Manufacturers are looked up again from the OUI database on load, while everything learned from the air is kept so that a resumed capture picks up where it left off.
+///
+/// Taking a snapshot is separate from saving it so that a shared device list only needs to be locked while it is copied.
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    version: u32,
+    /// The wall-clock time the session was saved at
+    saved: SystemTime,
+    devices: Vec<SavedDevice>,
+    /// The randomised addresses recognised as the same physical devices
+    #[serde(default)]
+    physical: Vec<PhysicalDevice>,
+    #[serde(default)]
+    associations: Vec<SavedAssociation>
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedAssociation {
+    station: MacAddress,
+    bssid: MacAddress,
+    association: Association
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedDevice {
+    address: MacAddress,
+    beacon: Option<String>,
+    uncloaked: Option<Uncloaked>,
+    sent: Option<Transmission>,
+    knows: Vec<MacAddress>,
+    probes: HashMap<String, Probe>,
+    /// Missing from sessions saved before clients were tracked
+    #[serde(default)]
+    bss: Option<MacAddress>,
+    #[serde(default)]
+    first_sent: Option<SystemTime>,
+    #[serde(default)]
+    frames: u64,
+    #[serde(default)]
+    fingerprint: Option<Fingerprint>,
+    #[serde(default)]
+    role: Option<Role>,
+    #[serde(default)]
+    history: VecDeque<Transmission>,
+    #[serde(default)]
+    security: Option<SecurityProfile>,
+    #[serde(default)]
+    phy: Option<PhyProfile>,
+    #[serde(default)]
+    channel: Option<Channel>
+}
+
+/// Load a session into the device list, leaving it untouched if the file does not exist yet
+pub fn load(path: &Path, devices: &mut DeviceList, oui_db: &OuiDatabase) -> Result<(), Error> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error.into())
+    };
+    let Header { version } = serde_json::from_slice(&data)?;
+    if version > VERSION {
+        return Err(Error::UnsupportedVersion(version))
+    }
+    let session: Session = serde_json::from_slice(&data)?;
+    // Sessions saved before group addresses were told apart from devices may still hold them
+    for saved in session.devices.into_iter().filter(|saved| AddressClass::of(&saved.address).is_individual()) {
+        let mut device = KnownDevice::new(saved.address, oui_db);
+        if let Some(ssid) = saved.beacon {
+            device.beacon(ssid);
+        }
+        device.uncloaked = saved.uncloaked;
+        device.sent = saved.sent;
+        device.knows = saved.knows.into_iter().filter(|address| AddressClass::of(address).is_individual()).collect();
+        device.probes = saved.probes;
+        device.bss = saved.bss;
+        device.first_sent = saved.first_sent;
+        device.frames = saved.frames;
+        device.fingerprint = saved.fingerprint;
+        device.role = saved.role;
+        device.history = saved.history;
+        device.security = saved.security;
+        device.phy = saved.phy;
+        device.channel = saved.channel;
+        devices.insert(saved.address, device);
+    }
+    for physical in session.physical {
+        devices.restore(physical);
+    }
+    for SavedAssociation { station, bssid, association } in session.associations {
+        devices.restore_association(station, bssid, association);
+    }
+    Ok(())
+}
+
+/// Save the device list, replacing the file only once the new session is completely written
+pub fn save(path: &Path, devices: &DeviceList) -> Result<(), Error> {
+    Session::new(devices).save(path)
+}
+
+impl Session {
+    /// Take a snapshot of the device list
+    pub fn new(devices: &DeviceList) -> Self {
+        Self {
+            version: VERSION,
+            saved: SystemTime::now(),
+            devices: devices.iter().map(|(&address, device)| SavedDevice {
+                address,
+                beacon: device.beacon.clone(),
+                uncloaked: device.uncloaked.clone(),
+                sent: device.sent.clone(),
+                knows: device.knows.iter().copied().collect(),
+                probes: device.probes.clone(),
+                bss: device.bss,
+                first_sent: device.first_sent,
+                frames: device.frames,
+                fingerprint: device.fingerprint,
+                role: device.role,
+                history: device.history.clone(),
+                security: device.security.clone(),
+                phy: device.phy.clone(),
+                channel: device.channel
+            }).collect(),
+            physical: devices.physical_devices().to_vec(),
+            associations: devices.all_associations().map(|(station, bssid, association)| SavedAssociation {
+                station,
+                bssid,
+                association: association.clone()
+            }).collect()
+        }
+    }
+    /// Write the snapshot, replacing the file only once it is completely written
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, serde_json::to_vec(self)?)?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use std::{sync::{Arc, Mutex, mpsc::{self, Receiver, Sender}}, thread::{self, JoinHandle}, time::{Duration, Instant, SystemTime}};
+use oui::OuiDatabase;
+use blockade_recon::{capture, source::Source, DeviceList};
+
+/// The longest time to hold on to the device list while handling packets
+const PACKET_BUDGET: Duration = Duration::from_millis(20);
+/// How long to let go of the device list after using the whole budget, so that the UI gets a turn
+const HANDOFF_INTERVAL: Duration = Duration::from_millis(1);
+/// How long to wait for packets when none are ready
+const IDLE_INTERVAL: Duration = Duration::from_millis(10);
+/// How often to report the state of the capture
+const STATUS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Requests from the UI to the capture thread
+pub enum Command {
+    /// Attach a comment to the next saved packet
+    Annotate(String),
+    SetSpeed(capture::Speed),
+    TogglePause,
+    /// Flush the capture file and stop capturing
+    Stop
+}
+
+/// The state of a replay as last seen by the capture thread
+#[derive(Debug, Clone, Copy)]
+pub struct ReplayStatus {
+    pub speed: capture::Speed,
+    pub paused: bool,
+    pub finished: bool
+}
+
+/// The state of the capture as last seen by the capture thread
+#[derive(Debug, Clone, Copy)]
+pub struct Status {
+    /// The current time as seen by the capture source
+    pub now: SystemTime,
+    pub replay: Option<ReplayStatus>,
+    pub comment_pending: bool,
+    /// The counts kept by libpcap when sniffing an interface
+    pub stats: Option<pcap::Stat>,
+    /// Roughly how large the current capture file is
+    pub saved_size: Option<u64>
+}
+impl Status {
+    fn new(source: &capture::Source, stats: Option<pcap::Stat>) -> Self {
+        Self {
+            now: source.now(),
+            replay: match source {
+                capture::Source::Replay(replay) => Some(ReplayStatus {
+                    speed: replay.speed(),
+                    paused: replay.paused(),
+                    finished: replay.finished()
+                }),
+                _ => None
+            },
+            comment_pending: source.comment_pending(),
+            stats,
+            saved_size: source.saved_size()
+        }
+    }
+}
+
+/// What the capture thread reports back to the UI
+pub enum Update {
+    /// Packets were handled and the devices may have changed
+    Processed,
+    Status(Status),
+    /// The capture thread stopped because of an error
+    Failed(Failure)
+}
+
+/// An error that stopped the capture thread
+#[derive(Debug)]
+pub struct Failure {
+    pub location: String,
+    pub message: &'static str,
+    pub reason: String
+}
+
+/// Report an error to the UI and stop the capture thread
+macro_rules! attempt {
+    ($updates:expr => $result:expr, $msg:expr) => {
+        match $result {
+            Ok(t) => t,
+            Err(e) => {
+                let location = format!("{}:{}:{}", file!(), line!(), column!());
+                let _ = $updates.send(Update::Failed(Failure { location, message: $msg, reason: e.to_string() }));
+                return
+            }
+        }
+    };
+}
+
+/// Captures and handles packets on a separate thread so that the UI never holds up the capture
+pub struct Sniffer {
+    pub commands: Sender<Command>,
+    pub updates: Receiver<Update>,
+    thread: JoinHandle<()>
+}
+impl Sniffer {
+    pub fn spawn(source: capture::Source, devices: Arc<Mutex<DeviceList>>, oui_db: OuiDatabase) -> Self {
+        let (commands, command_rx) = mpsc::channel();
+        let (update_tx, updates) = mpsc::channel();
+        let thread = thread::spawn(move || run(source, devices, oui_db, command_rx, update_tx));
+        Self {
+            commands,
+            updates,
+            thread
+        }
+    }
+    /// Stop capturing once the capture file is flushed
+    pub fn stop(self) -> Result<(), Failure> {
+        // The thread may have already stopped after an error
+        let _ = self.commands.send(Command::Stop);
+        self.thread.join().expect("The capture thread panicked");
+        match self.updates.try_iter().find_map(|update| if let Update::Failed(failure) = update { Some(failure) } else { None }) {
+            Some(failure) => Err(failure),
+            None => Ok(())
+        }
+    }
+}
+
+fn run(mut source: capture::Source, devices: Arc<Mutex<DeviceList>>, oui_db: OuiDatabase, commands: Receiver<Command>, updates: Sender<Update>) {
+    let mut last_status = None;
+    loop {
+        for command in commands.try_iter() {
+            match command {
+                Command::Annotate(comment) => source.annotate(comment),
+                Command::SetSpeed(speed) => if let capture::Source::Replay(replay) = &mut source {
+                    replay.set_speed(speed)
+                },
+                Command::TogglePause => if let capture::Source::Replay(replay) = &mut source {
+                    replay.toggle_pause()
+                },
+                Command::Stop => {
+                    attempt!(updates => source.flush(), "Unable to flush the capture file");
+                    return
+                }
+            }
+        }
+
+        // Handle as many packets as are ready without keeping the UI from the devices for too long
+        let mut processed = false;
+        let mut exhausted = false;
+        {
+            let mut devices = devices.lock().unwrap();
+            let started = Instant::now();
+            loop {
+                if started.elapsed() >= PACKET_BUDGET {
+                    exhausted = true;
+                    break
+                }
+                let packet = match attempt!(updates => source.next(), "Unable to get next packet") {
+                    Some(packet) => packet,
+                    None => break
+                };
+                // Malformed packets are counted and shown, rather than stopping the capture
+                let _ = devices.process_packet(&packet, &oui_db);
+                processed = true
+            }
+        }
+        // The mutex is not fair, so relocking straight away under heavy traffic could keep a waiting UI out indefinitely
+        if exhausted {
+            thread::sleep(HANDOFF_INTERVAL)
+        }
+        if processed && updates.send(Update::Processed).is_err() {
+            // The UI has gone away
+            return
+        }
+
+        if last_status.map_or(true, |last: Instant| last.elapsed() >= STATUS_INTERVAL) {
+            let stats = attempt!(updates => source.stats(), "Unable to get capture statistics");
+            if updates.send(Update::Status(Status::new(&source, stats))).is_err() {
+                return
+            }
+            last_status = Some(Instant::now())
+        }
+
+        attempt!(updates => source.tick(), "Unable to save to the capture file");
+        if !processed {
+            thread::sleep(IDLE_INTERVAL)
+        }
+    }
+}
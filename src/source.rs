@@ -0,0 +1,26 @@
+//! Where captured frames come from, independent of how they are captured
+
+use std::time::SystemTime;
+
+/// A captured frame, starting with its radiotap header
+pub trait Packet {
+    /// The wall-clock time that the packet was captured at
+    fn time(&self) -> SystemTime;
+    fn data(&self) -> &[u8];
+}
+
+/// Something that packets can be read from, such as an interface or a capture file
+pub trait Source {
+    type Packet: Packet;
+    type Error: std::error::Error;
+    /// Get the next packet that is ready to be processed, if any
+    fn next(&mut self) -> Result<Option<Self::Packet>, Self::Error>;
+    /// The current time as seen by the source
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+    /// Whether the source has run out of packets for good
+    fn finished(&self) -> bool {
+        false
+    }
+}
@@ -59,13 +59,37 @@ impl Ui {
     }
 }
 
-pub struct ListState{
-    state: tui::widgets::ListState,
+/// The state of a widget that has a selected item
+pub trait Selectable: Default {
+    fn selected(&self) -> Option<usize>;
+    fn select(&mut self, index: Option<usize>);
+}
+impl Selectable for tui::widgets::ListState {
+    fn selected(&self) -> Option<usize> {
+        self.selected()
+    }
+    fn select(&mut self, index: Option<usize>) {
+        self.select(index)
+    }
+}
+impl Selectable for tui::widgets::TableState {
+    fn selected(&self) -> Option<usize> {
+        self.selected()
+    }
+    fn select(&mut self, index: Option<usize>) {
+        self.select(index)
+    }
+}
+
+pub struct ListState<S: Selectable = tui::widgets::ListState> {
+    state: S,
     item_count: usize
 }
-impl ListState {
+/// Selection in a table, which moves the same way as in a list
+pub type TableState = ListState<tui::widgets::TableState>;
+impl<S: Selectable> ListState<S> {
     pub fn with_item_count(item_count: usize) -> Self {
-        let mut state = tui::widgets::ListState::default();
+        let mut state = S::default();
         state.select(Some(0));
         Self {
             state,
@@ -105,23 +129,18 @@ impl ListState {
         self.state.select(Some(self.item_count.saturating_sub(1)))
     }
 }
-impl Default for ListState {
+impl<S: Selectable> Default for ListState<S> {
     fn default() -> Self {
-        let mut state = tui::widgets::ListState::default();
-        state.select(Some(0));
-        Self {
-            state,
-            item_count: 0
-        }
+        Self::with_item_count(0)
     }
 }
-impl Deref for ListState {
-    type Target = tui::widgets::ListState;
+impl<S: Selectable> Deref for ListState<S> {
+    type Target = S;
     fn deref(&self) -> &Self::Target {
         &self.state
     }
 }
-impl DerefMut for ListState {
+impl<S: Selectable> DerefMut for ListState<S> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.state
     }
@@ -157,6 +176,53 @@ impl<'a> TabState<'a> {
     }
 }
 
+/// What became of a prompt after a key press
+pub enum PromptEvent {
+    Editing,
+    Submit(String),
+    Cancel
+}
+
+/// A line of text being typed in by the user
+pub struct Prompt {
+    pub title: &'static str,
+    pub text: String
+}
+impl Prompt {
+    pub fn new(title: &'static str) -> Self {
+        Self::with_text(title, String::new())
+    }
+    /// Create a prompt with text ready to be edited
+    pub fn with_text(title: &'static str, text: String) -> Self {
+        Self {
+            title,
+            text
+        }
+    }
+    pub fn key(&mut self, key: Key) -> PromptEvent {
+        match key {
+            Key::Char('\n') => PromptEvent::Submit(std::mem::take(&mut self.text)),
+            Key::Esc => PromptEvent::Cancel,
+            Key::Backspace => {
+                self.text.pop();
+                PromptEvent::Editing
+            },
+            Key::Char(c) if !c.is_control() => {
+                self.text.push(c);
+                PromptEvent::Editing
+            },
+            _ => PromptEvent::Editing
+        }
+    }
+    pub fn widget(&self) -> Paragraph {
+        Paragraph::new(Spans::from(vec![
+            Span::raw(self.text.as_str()),
+            Span::styled(" ", Style::default().add_modifier(Modifier::REVERSED))
+        ]))
+            .block(Block::default().borders(Borders::ALL).title(self.title))
+    }
+}
+
 pub struct Input {
     pub stdin: Receiver<Key>,
 }
@@ -1,4 +1,19 @@
 use eui48::MacAddress;
+use serde::{Deserialize, Serialize};
+
+mod capabilities;
+mod channel;
+mod fingerprint;
+mod security;
+
+pub use capabilities::{
+    ChannelWidth, Generation, PhyProfile,
+    HtCapabilities, HtOperation, VhtCapabilities, VhtOperation,
+    HeCapabilities, HeOperation, EhtCapabilities, EhtOperation
+};
+pub use channel::{Band, Channel};
+pub use fingerprint::Fingerprint;
+pub use security::{Akm, CipherSuite, Rsn, Security, SecurityProfile};
 
 macro_rules! mac {
     ($bytes:expr => $start:expr) => {
@@ -12,6 +27,14 @@ macro_rules! mac {
         ])
     };
 }
+macro_rules! u16 {
+    (le[$bytes:expr => $start:expr]) => {
+        u16::from_le_bytes([
+            $bytes[$start],
+            $bytes[$start + 1]
+        ])
+    };
+}
 macro_rules! u64 {
     (le[$bytes:expr => $start:expr]) => {
         u64::from_le_bytes([
@@ -27,6 +50,9 @@ macro_rules! u64 {
     };
 }
 
+/// The frame control flag set when the body is encrypted
+const FLAG_PROTECTED: u8 = 1 << 6;
+
 #[derive(Debug)]
 pub enum FrameType {
     Control(ControlFrame),
@@ -37,8 +63,17 @@ pub enum FrameType {
 impl FrameType {
     fn new(ty: u8, subty: u8, flags: u8, address1: MacAddress, frame: &[u8]) -> Result<Self> {
         match (ty, subty) {
+            (0, 0) => ManagementFrame::association_request(frame, address1),
+            (0, 1) => ManagementFrame::association_response(frame, address1, false),
+            (0, 2) => ManagementFrame::reassociation_request(frame, address1),
+            (0, 3) => ManagementFrame::association_response(frame, address1, true),
+            (0, 4) => ManagementFrame::probe_request(frame, address1),
+            (0, 5) => ManagementFrame::probe_response(frame, address1),
             (0, 8) => ManagementFrame::beacon(frame, address1),
-            (1, 13) => ControlFrame::ack(),
+            (0, 10) => ManagementFrame::disassociation(frame, flags, address1),
+            (0, 11) => ManagementFrame::authentication(frame, address1),
+            (0, 12) => ManagementFrame::deauthentication(frame, flags, address1),
+            (1, _) => ControlFrame::parse(subty, frame, address1),
             (2, _) => DataFrame::data(frame, flags, address1),
             _ => Err(Error::UnrecognisedFrameType)
         }
@@ -49,10 +84,21 @@ pub struct Frame<'a> {
     pub frame_type: FrameType,
     pub duration: u16,
     pub body: &'a [u8],
-    pub fcs: u32
+    pub fcs: Option<u32>
 }
 impl<'a> Frame<'a> {
-    pub fn new(mut frame: &'a [u8]) -> Result<Self> {
+    /// Parse a frame, verifying and stripping the trailing FCS if the capture includes it
+    pub fn new(frame: &'a [u8], fcs: bool) -> Result<Self> {
+        let (frame, fcs) = if fcs {
+            let (frame, fcs) = frame.split_at(frame.len().checked_sub(4).ok_or(Error::UnexpectedEof)?);
+            let fcs = u32::from_le_bytes([fcs[0], fcs[1], fcs[2], fcs[3]]);
+            if crc32fast::hash(frame) != fcs {
+                return Err(Error::BadFcs)
+            }
+            (frame, Some(fcs))
+        } else {
+            (frame, None)
+        };
         if frame.len() < 10 {
             return Err(Error::UnexpectedEof)
         }
@@ -70,17 +116,12 @@ impl<'a> Frame<'a> {
             (frame_control >> 4) & 0b1111,
             flags,
             address1,
-            &mut frame
+            frame
         )?;
-        let fcs = u32::from_le_bytes({
-            let b = &frame[frame.len() - 4..];
-            [b[0], b[1], b[2], b[3]]
-        });
-        let body = &frame[..frame.len() - 4];
         Ok(Self {
             frame_type,
             duration,
-            body,
+            body: frame,
             fcs
         })
     }
@@ -88,11 +129,125 @@ impl<'a> Frame<'a> {
 
 #[derive(Debug)]
 pub enum ControlFrame {
-    Ack
+    Trigger {
+        receiver: MacAddress,
+        transmitter: MacAddress
+    },
+    Tack {
+        receiver: MacAddress,
+        transmitter: MacAddress
+    },
+    BeamformingReportPoll {
+        receiver: MacAddress,
+        transmitter: MacAddress
+    },
+    NdpAnnouncement {
+        receiver: MacAddress,
+        transmitter: MacAddress
+    },
+    Extension {
+        receiver: MacAddress
+    },
+    Wrapper {
+        receiver: MacAddress
+    },
+    BlockAckRequest {
+        receiver: MacAddress,
+        transmitter: MacAddress,
+        control: u16,
+        starting_sequence: u16
+    },
+    BlockAck {
+        receiver: MacAddress,
+        transmitter: MacAddress,
+        control: u16
+    },
+    PsPoll {
+        /// The association ID of the station waking up
+        aid: u16,
+        bssid: MacAddress,
+        transmitter: MacAddress
+    },
+    Rts {
+        receiver: MacAddress,
+        transmitter: MacAddress
+    },
+    Cts {
+        receiver: MacAddress
+    },
+    Ack {
+        receiver: MacAddress
+    },
+    CfEnd {
+        receiver: MacAddress,
+        bssid: MacAddress
+    },
+    CfEndAck {
+        receiver: MacAddress,
+        bssid: MacAddress
+    }
 }
 impl ControlFrame {
-    fn ack() -> Result<FrameType> {
-        Ok(FrameType::Control(Self::Ack))
+    fn parse(subtype: u8, frame: &[u8], receiver: MacAddress) -> Result<FrameType> {
+        if frame.len() < 10 {
+            return Err(Error::UnexpectedEof)
+        }
+        let transmitter = || if frame.len() < 16 {
+            Err(Error::UnexpectedEof)
+        } else {
+            let mut transmitter = [0; 6];
+            transmitter.copy_from_slice(&frame[10..16]);
+            // The group bit is borrowed to signal bandwidth and is never set on a real transmitter address
+            transmitter[0] &= !1;
+            Ok(MacAddress::new(transmitter))
+        };
+        Ok(FrameType::Control(match subtype {
+            2 => Self::Trigger { receiver, transmitter: transmitter()? },
+            3 => Self::Tack { receiver, transmitter: transmitter()? },
+            4 => Self::BeamformingReportPoll { receiver, transmitter: transmitter()? },
+            5 => Self::NdpAnnouncement { receiver, transmitter: transmitter()? },
+            6 => Self::Extension { receiver },
+            7 => Self::Wrapper { receiver },
+            8 if frame.len() >= 20 => Self::BlockAckRequest {
+                receiver,
+                transmitter: transmitter()?,
+                control: u16!(le[frame => 16]),
+                starting_sequence: u16!(le[frame => 18]) >> 4
+            },
+            9 if frame.len() >= 18 => Self::BlockAck {
+                receiver,
+                transmitter: transmitter()?,
+                control: u16!(le[frame => 16])
+            },
+            8 | 9 => return Err(Error::UnexpectedEof),
+            // The AID takes the place of the duration
+            10 => Self::PsPoll { aid: u16!(le[frame => 2]) & 0x3fff, bssid: receiver, transmitter: transmitter()? },
+            11 => Self::Rts { receiver, transmitter: transmitter()? },
+            12 => Self::Cts { receiver },
+            13 => Self::Ack { receiver },
+            14 => Self::CfEnd { receiver, bssid: transmitter()? },
+            15 => Self::CfEndAck { receiver, bssid: transmitter()? },
+            _ => return Err(Error::UnrecognisedFrameType)
+        }))
+    }
+    /// The receiver and, if present, the transmitter of the frame
+    pub fn addresses(&self) -> (MacAddress, Option<MacAddress>) {
+        match *self {
+            Self::Trigger { receiver, transmitter }
+                | Self::Tack { receiver, transmitter }
+                | Self::BeamformingReportPoll { receiver, transmitter }
+                | Self::NdpAnnouncement { receiver, transmitter }
+                | Self::BlockAckRequest { receiver, transmitter, .. }
+                | Self::BlockAck { receiver, transmitter, .. }
+                | Self::Rts { receiver, transmitter }
+                | Self::CfEnd { receiver, bssid: transmitter }
+                | Self::CfEndAck { receiver, bssid: transmitter } => (receiver, Some(transmitter)),
+            Self::PsPoll { bssid, transmitter, .. } => (bssid, Some(transmitter)),
+            Self::Extension { receiver }
+                | Self::Wrapper { receiver }
+                | Self::Cts { receiver }
+                | Self::Ack { receiver } => (receiver, None)
+        }
     }
 }
 
@@ -100,30 +255,71 @@ impl ControlFrame {
 pub enum ManagementTag {
     Ssid(String),
     SupportedRates(Vec<u8>),
+    DsParameterSet {
+        channel: u8
+    },
     Country {
         code: [u8; 2],
     },
+    /// The name of an 802.11s mesh, sent in place of an SSID by mesh stations
+    MeshId(String),
+    HtCapabilities(HtCapabilities),
+    HtOperation(HtOperation),
+    VhtCapabilities(VhtCapabilities),
+    VhtOperation(VhtOperation),
+    HeCapabilities(HeCapabilities),
+    HeOperation(HeOperation),
+    /// Only sent by devices able to use the 6 GHz band
+    He6GhzCapabilities,
+    EhtCapabilities(EhtCapabilities),
+    EhtOperation(EhtOperation),
+    Rsn(Rsn),
+    /// Microsoft's WPA element, which predates RSN
+    Wpa(Rsn),
+    /// An RSN or WPA element that could not be decoded, which still shows that the network is not using WEP
+    MalformedRsn,
     VendorSpecific {
         vendor: [u8; 3],
     },
+    /// An element that is not understood, or could not be decoded
     Unknown
 }
 impl ManagementTag {
     /// Parse a single management tag, removing itself from the start of the given buffer
+    ///
+    /// An element that cannot be decoded is Unknown, or MalformedRsn for the security elements, so that one quirky element does not lose the whole frame.
+    /// Only an element that runs past the end of the buffer is an error.
     pub fn parse(data: &mut &[u8]) -> Result<Self> {
         let &length = data.get(1).ok_or(Error::UnexpectedEof)?;
         let tag = data[0];
-        let (d, other) = data.split_at(2 + length as usize);
+        let end = 2 + length as usize;
+        if data.len() < end {
+            return Err(Error::UnexpectedEof)
+        }
+        let (d, other) = data.split_at(end);
         *data = other;
         let data = &d[2..];
         Ok(match tag {
             0x00 => Self::Ssid(String::from_utf8_lossy(data).to_string()),
             0x01 => Self::SupportedRates(data.to_vec()),
-            0x07 => Self::Country {
-                code: [data[0], data[1]]
-            },
-            0xdd => Self::VendorSpecific {
-                vendor: [data[0], data[1], data[2]]
+            0x03 => data.first().map_or(Self::Unknown, |&channel| Self::DsParameterSet { channel }),
+            0x07 => data.get(..2).map_or(Self::Unknown, |c| Self::Country { code: [c[0], c[1]] }),
+            0x2d => HtCapabilities::parse(data).map_or(Self::Unknown, Self::HtCapabilities),
+            0x30 => Rsn::parse(data).map_or(Self::MalformedRsn, Self::Rsn),
+            0x3d => HtOperation::parse(data).map_or(Self::Unknown, Self::HtOperation),
+            0x72 => Self::MeshId(String::from_utf8_lossy(data).to_string()),
+            0xbf => VhtCapabilities::parse(data).map_or(Self::Unknown, Self::VhtCapabilities),
+            0xc0 => VhtOperation::parse(data).map_or(Self::Unknown, Self::VhtOperation),
+            0xdd if data.len() >= 4 && data[..3] == security::WPA_OUI && data[3] == 1 => Rsn::parse_wpa(&data[4..]).map_or(Self::MalformedRsn, Self::Wpa),
+            0xdd => data.get(..3).map_or(Self::Unknown, |v| Self::VendorSpecific { vendor: [v[0], v[1], v[2]] }),
+            // Element ID extension, where the first byte gives the real ID
+            0xff => match data.split_first() {
+                Some((35, data)) => HeCapabilities::parse(data).map_or(Self::Unknown, Self::HeCapabilities),
+                Some((36, data)) => HeOperation::parse(data).map_or(Self::Unknown, Self::HeOperation),
+                Some((59, _)) => Self::He6GhzCapabilities,
+                Some((106, data)) => EhtOperation::parse(data).map_or(Self::Unknown, Self::EhtOperation),
+                Some((108, data)) => EhtCapabilities::parse(data).map_or(Self::Unknown, Self::EhtCapabilities),
+                _ => Self::Unknown
             },
             _ => Self::Unknown
         })
@@ -136,14 +332,169 @@ impl ManagementTag {
         }
         Ok(tags)
     }
+    /// Find the SSID within a list of tags
+    fn ssid(tags: &[Self]) -> Result<String> {
+        tags.iter().find_map(|t| if let Self::Ssid(ssid) = t { Some(ssid.clone()) } else { None }).ok_or(Error::MissingTag("SSID"))
+    }
+    /// Find the supported rates within a list of tags
+    fn supported_rates(tags: &[Self]) -> Result<Vec<u8>> {
+        tags.iter().find_map(|t| if let Self::SupportedRates(rates) = t { Some(rates.clone()) } else { None }).ok_or(Error::MissingTag("Supported Rates"))
+    }
 }
 #[derive(Debug)]
 pub enum ManagementFields {
     Beacon {
         timestamp: u64,
+        interval: u16,
+        capabilities: u16,
         ssid: String,
         supported_rates: Vec<u8>,
         tags: Vec<ManagementTag>
+    },
+    ProbeRequest {
+        /// The network being searched for, or empty for a wildcard probe
+        ssid: String,
+        tags: Vec<ManagementTag>,
+        fingerprint: Fingerprint
+    },
+    ProbeResponse {
+        timestamp: u64,
+        interval: u16,
+        capabilities: u16,
+        ssid: String,
+        supported_rates: Vec<u8>,
+        tags: Vec<ManagementTag>
+    },
+    AssociationRequest {
+        capabilities: u16,
+        listen_interval: u16,
+        ssid: String,
+        tags: Vec<ManagementTag>
+    },
+    ReassociationRequest {
+        capabilities: u16,
+        listen_interval: u16,
+        /// The AP the station is moving away from
+        current_ap: MacAddress,
+        ssid: String,
+        tags: Vec<ManagementTag>
+    },
+    /// A response to an association or reassociation request
+    AssociationResponse {
+        reassociation: bool,
+        capabilities: u16,
+        status: u16,
+        /// The association ID given to the station
+        aid: u16,
+        tags: Vec<ManagementTag>
+    },
+    Disassociation {
+        /// None if the frame is protected, as the reason is encrypted
+        reason: Option<u16>
+    },
+    Authentication {
+        algorithm: AuthAlgorithm,
+        /// The transaction sequence number within the authentication exchange
+        sequence: u16,
+        status: u16,
+        tags: Vec<ManagementTag>
+    },
+    Deauthentication {
+        /// None if the frame is protected, as the reason is encrypted
+        reason: Option<u16>
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuthAlgorithm {
+    OpenSystem,
+    SharedKey,
+    FastBssTransition,
+    Sae,
+    Fils,
+    Other(u16)
+}
+impl From<u16> for AuthAlgorithm {
+    fn from(algorithm: u16) -> Self {
+        match algorithm {
+            0 => Self::OpenSystem,
+            1 => Self::SharedKey,
+            2 => Self::FastBssTransition,
+            3 => Self::Sae,
+            4 => Self::Fils,
+            other => Self::Other(other)
+        }
+    }
+}
+impl std::fmt::Display for AuthAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::OpenSystem => write!(f, "Open System"),
+            Self::SharedKey => write!(f, "Shared Key"),
+            Self::FastBssTransition => write!(f, "Fast BSS Transition"),
+            Self::Sae => write!(f, "SAE"),
+            Self::Fils => write!(f, "FILS"),
+            Self::Other(algorithm) => write!(f, "Unknown ({})", algorithm)
+        }
+    }
+}
+
+/// Describe the status code of an authentication or association response
+pub fn status_description(status: u16) -> &'static str {
+    match status {
+        0 => "Success",
+        1 => "Unspecified failure",
+        10 => "Cannot support all requested capabilities",
+        11 => "Reassociation denied, no existing association",
+        12 => "Association denied for an unspecified reason",
+        13 => "Authentication algorithm not supported",
+        14 => "Unexpected authentication sequence number",
+        15 => "Challenge failure",
+        16 => "Authentication timeout",
+        17 => "AP is full",
+        18 => "Basic rates not supported",
+        30 => "Rejected temporarily, try again later",
+        31 => "Management frame protection policy violation",
+        37 => "Request declined",
+        40 => "Invalid element",
+        41 => "Invalid group cipher",
+        42 => "Invalid pairwise cipher",
+        43 => "Invalid AKM",
+        45 => "Invalid RSN capabilities",
+        53 => "Invalid PMKID",
+        76 => "Anti-clogging token required",
+        77 => "Finite cyclic group not supported",
+        _ => "Unknown"
+    }
+}
+
+/// Describe the reason code of a deauthentication or disassociation
+pub fn reason_description(reason: u16) -> &'static str {
+    match reason {
+        1 => "Unspecified",
+        2 => "Previous authentication no longer valid",
+        3 => "Station is leaving the ESS",
+        4 => "Inactivity",
+        5 => "AP is full",
+        6 => "Class 2 frame from an unauthenticated station",
+        7 => "Class 3 frame from an unassociated station",
+        8 => "Station is leaving the BSS",
+        9 => "Station is not authenticated",
+        10 => "Power capability unacceptable",
+        11 => "Supported channels unacceptable",
+        13 => "Invalid element",
+        14 => "MIC failure",
+        15 => "4-way handshake timeout",
+        16 => "Group key handshake timeout",
+        17 => "4-way handshake element mismatch",
+        18 => "Invalid group cipher",
+        19 => "Invalid pairwise cipher",
+        20 => "Invalid AKM",
+        21 => "Unsupported RSN version",
+        22 => "Invalid RSN capabilities",
+        23 => "802.1X authentication failed",
+        24 => "Cipher suite rejected",
+        _ => "Unknown"
     }
 }
 #[derive(Debug)]
@@ -160,29 +511,144 @@ impl ManagementFrame {
             receiver,
             transmitter: mac!(frame => 10),
             bssid: mac!(frame => 16),
-            sequence_control: u16::from_le_bytes([frame[22], frame[23]]),
+            sequence_control: u16!(le[frame => 22]),
             fields
         }
     }
+    /// Get the frame body, ensuring it is long enough to hold the fixed fields
+    fn body(frame: &[u8], fixed: usize) -> Result<&[u8]> {
+        if frame.len() < 24 + fixed {
+            Err(Error::UnexpectedEof)
+        } else {
+            Ok(&frame[24..])
+        }
+    }
     fn beacon(frame: &[u8], receiver: MacAddress) -> Result<FrameType> {
-        if frame.len() < 40 {
+        if frame.len() < 36 {
             Err(Error::UnexpectedEof)
         } else {
-            let data = &frame[24..frame.len() - 4];
+            let data = &frame[24..];
             let tags = ManagementTag::parse_all(&data[12..])?;
             let fields = ManagementFields::Beacon {
                 timestamp: u64!(le[data => 0]),
-                ssid: tags.iter().find_map(|t| if let ManagementTag::Ssid(ssid) = t { Some(ssid.clone()) } else { None }).ok_or(Error::MissingTag("SSID"))?,
-                supported_rates: tags.iter().find_map(|t| if let ManagementTag::SupportedRates(rates) = t { Some(rates.clone()) } else { None }).ok_or(Error::MissingTag("Supported Rates"))?,
+                interval: u16!(le[data => 8]),
+                capabilities: u16!(le[data => 10]),
+                ssid: ManagementTag::ssid(&tags)?,
+                supported_rates: ManagementTag::supported_rates(&tags)?,
+                tags
+            };
+            Ok(FrameType::Management(Self::new(frame, receiver, fields)))
+        }
+    }
+    fn probe_response(frame: &[u8], receiver: MacAddress) -> Result<FrameType> {
+        // Probe responses share the layout of a beacon
+        if frame.len() < 36 {
+            Err(Error::UnexpectedEof)
+        } else {
+            let data = &frame[24..];
+            let tags = ManagementTag::parse_all(&data[12..])?;
+            let fields = ManagementFields::ProbeResponse {
+                timestamp: u64!(le[data => 0]),
+                interval: u16!(le[data => 8]),
+                capabilities: u16!(le[data => 10]),
+                ssid: ManagementTag::ssid(&tags)?,
+                supported_rates: ManagementTag::supported_rates(&tags)?,
                 tags
             };
             Ok(FrameType::Management(Self::new(frame, receiver, fields)))
         }
     }
+    fn probe_request(frame: &[u8], receiver: MacAddress) -> Result<FrameType> {
+        if frame.len() < 24 {
+            Err(Error::UnexpectedEof)
+        } else {
+            let tags = ManagementTag::parse_all(&frame[24..])?;
+            let fields = ManagementFields::ProbeRequest {
+                ssid: ManagementTag::ssid(&tags)?,
+                tags,
+                fingerprint: Fingerprint::new(&frame[24..])
+            };
+            Ok(FrameType::Management(Self::new(frame, receiver, fields)))
+        }
+    }
+    fn association_request(frame: &[u8], receiver: MacAddress) -> Result<FrameType> {
+        let data = Self::body(frame, 4)?;
+        let tags = ManagementTag::parse_all(&data[4..])?;
+        let fields = ManagementFields::AssociationRequest {
+            capabilities: u16!(le[data => 0]),
+            listen_interval: u16!(le[data => 2]),
+            ssid: ManagementTag::ssid(&tags)?,
+            tags
+        };
+        Ok(FrameType::Management(Self::new(frame, receiver, fields)))
+    }
+    fn reassociation_request(frame: &[u8], receiver: MacAddress) -> Result<FrameType> {
+        let data = Self::body(frame, 10)?;
+        let tags = ManagementTag::parse_all(&data[10..])?;
+        let fields = ManagementFields::ReassociationRequest {
+            capabilities: u16!(le[data => 0]),
+            listen_interval: u16!(le[data => 2]),
+            current_ap: mac!(data => 4),
+            ssid: ManagementTag::ssid(&tags)?,
+            tags
+        };
+        Ok(FrameType::Management(Self::new(frame, receiver, fields)))
+    }
+    fn association_response(frame: &[u8], receiver: MacAddress, reassociation: bool) -> Result<FrameType> {
+        let data = Self::body(frame, 6)?;
+        let fields = ManagementFields::AssociationResponse {
+            reassociation,
+            capabilities: u16!(le[data => 0]),
+            status: u16!(le[data => 2]),
+            // The top two bits are always set
+            aid: u16!(le[data => 4]) & 0x3fff,
+            tags: ManagementTag::parse_all(&data[6..])?
+        };
+        Ok(FrameType::Management(Self::new(frame, receiver, fields)))
+    }
+    /// Read the reason code that starts the body of a disassociation or deauthentication, unless it is encrypted
+    fn reason(frame: &[u8], flags: u8) -> Result<Option<u16>> {
+        // With management frame protection the body starts with the CCMP header instead
+        if flags & FLAG_PROTECTED != 0 {
+            Self::body(frame, 0)?;
+            Ok(None)
+        } else {
+            let data = Self::body(frame, 2)?;
+            Ok(Some(u16!(le[data => 0])))
+        }
+    }
+    fn disassociation(frame: &[u8], flags: u8, receiver: MacAddress) -> Result<FrameType> {
+        let fields = ManagementFields::Disassociation {
+            reason: Self::reason(frame, flags)?
+        };
+        Ok(FrameType::Management(Self::new(frame, receiver, fields)))
+    }
+    fn authentication(frame: &[u8], receiver: MacAddress) -> Result<FrameType> {
+        let data = Self::body(frame, 6)?;
+        let algorithm = AuthAlgorithm::from(u16!(le[data => 0]));
+        let fields = ManagementFields::Authentication {
+            algorithm,
+            sequence: u16!(le[data => 2]),
+            status: u16!(le[data => 4]),
+            // SAE carries its commit and confirm messages in place of tags
+            tags: if algorithm == AuthAlgorithm::Sae { vec![] } else { ManagementTag::parse_all(&data[6..])? }
+        };
+        Ok(FrameType::Management(Self::new(frame, receiver, fields)))
+    }
+    fn deauthentication(frame: &[u8], flags: u8, receiver: MacAddress) -> Result<FrameType> {
+        let fields = ManagementFields::Deauthentication {
+            reason: Self::reason(frame, flags)?
+        };
+        Ok(FrameType::Management(Self::new(frame, receiver, fields)))
+    }
 }
 
 #[derive(Debug)]
 pub struct DataFrame {
+    /// Whether the frame is headed into the distribution system, as from a station to its AP
+    pub to_ds: bool,
+    /// Whether the frame comes out of the distribution system, as from an AP to a station
+    pub from_ds: bool,
     pub receiver: MacAddress,
     pub transmitter: MacAddress,
     pub destination: MacAddress,
@@ -195,9 +661,8 @@ impl DataFrame {
         let to_ds = flags & 0b1 != 0;
         let from_ds = flags & 0b10 != 0;
 
-        if to_ds && from_ds && frame.len() < 40 {
-            Err(Error::UnexpectedEof)
-        } else if frame.len() < 34 {
+        // Frames between distribution systems carry a fourth address
+        if frame.len() < if to_ds && from_ds { 30 } else { 24 } {
             Err(Error::UnexpectedEof)
         } else {
             let transmitter = mac!(frame => 10);
@@ -210,6 +675,8 @@ impl DataFrame {
             };
             let sequence_control = u16::from_le_bytes([frame[22], frame[23]]);
             Ok(FrameType::Data(Self {
+                to_ds,
+                from_ds,
                 receiver,
                 transmitter,
                 destination,
@@ -227,6 +694,11 @@ pub enum ExtensionFrame {
 impl ExtensionFrame {
 }
 
+/// Whether an SSID has been blanked out to hide the network name
+pub fn hidden_ssid(ssid: &str) -> bool {
+    ssid.chars().all(|c| c == '\0')
+}
+
 type Result<T> = std::result::Result<T, Error>;
 #[derive(Debug)]
 pub enum Error {
@@ -234,6 +706,8 @@ pub enum Error {
     InvalidVersion(u8),
     UnrecognisedFrameType,
     MissingTag(&'static str),
+    /// The frame check sequence does not match the frame contents
+    BadFcs
 }
 impl From<eui48::ParseError> for Error {
     fn from(_: eui48::ParseError) -> Self {
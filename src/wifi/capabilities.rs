@@ -0,0 +1,409 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Error, ManagementTag, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ChannelWidth {
+    Mhz20,
+    Mhz40,
+    Mhz80,
+    Mhz160,
+    Mhz80Plus80,
+    Mhz320
+}
+impl ChannelWidth {
+    /// Interpret a channel width given as a pair of centre frequency segments, as done by VHT and HE operation elements
+    fn from_segments(center0: u8, center1: u8) -> Self {
+        let gap = (center1 as i16 - center0 as i16).abs();
+        if center1 == 0 {
+            Self::Mhz80
+        } else if gap == 8 {
+            Self::Mhz160
+        } else if gap > 16 {
+            Self::Mhz80Plus80
+        } else {
+            Self::Mhz80
+        }
+    }
+}
+impl std::fmt::Display for ChannelWidth {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Mhz20 => write!(f, "20 MHz"),
+            Self::Mhz40 => write!(f, "40 MHz"),
+            Self::Mhz80 => write!(f, "80 MHz"),
+            Self::Mhz160 => write!(f, "160 MHz"),
+            Self::Mhz80Plus80 => write!(f, "80+80 MHz"),
+            Self::Mhz320 => write!(f, "320 MHz")
+        }
+    }
+}
+
+/// The Wi-Fi generation, named after the newest amendment a device supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Generation {
+    /// 802.11a/b/g
+    Legacy,
+    /// 802.11n (HT)
+    WiFi4,
+    /// 802.11ac (VHT)
+    WiFi5,
+    /// 802.11ax (HE)
+    WiFi6,
+    /// 802.11ax on the 6 GHz band
+    WiFi6E,
+    /// 802.11be (EHT)
+    WiFi7
+}
+impl Generation {
+    pub const ALL: [Self; 6] = [Self::Legacy, Self::WiFi4, Self::WiFi5, Self::WiFi6, Self::WiFi6E, Self::WiFi7];
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Legacy => "Legacy",
+            Self::WiFi4 => "Wi-Fi 4",
+            Self::WiFi5 => "Wi-Fi 5",
+            Self::WiFi6 => "Wi-Fi 6",
+            Self::WiFi6E => "Wi-Fi 6E",
+            Self::WiFi7 => "Wi-Fi 7"
+        }
+    }
+}
+impl std::fmt::Display for Generation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Count the spatial streams in a VHT or HE MCS map, where each stream takes two bits and 3 marks it as unsupported
+fn mcs_map_streams(map: u16) -> u8 {
+    (0..8).rev().find(|n| (map >> (n * 2)) & 0b11 != 0b11).map(|n| n as u8 + 1).unwrap_or(0)
+}
+
+#[derive(Debug, Clone)]
+pub struct HtCapabilities {
+    pub info: u16,
+    /// The receive MCS bitmask for MCS 0 to 31, one byte for each spatial stream
+    pub rx_mcs: [u8; 4]
+}
+impl HtCapabilities {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 26 {
+            return Err(Error::UnexpectedEof)
+        }
+        Ok(Self {
+            info: u16::from_le_bytes([data[0], data[1]]),
+            rx_mcs: [data[3], data[4], data[5], data[6]]
+        })
+    }
+    pub fn spatial_streams(&self) -> u8 {
+        self.rx_mcs.iter().rposition(|&mcs| mcs != 0).map(|n| n as u8 + 1).unwrap_or(0)
+    }
+    pub fn width(&self) -> ChannelWidth {
+        if self.info & 0b10 != 0 { ChannelWidth::Mhz40 } else { ChannelWidth::Mhz20 }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HtOperation {
+    pub primary_channel: u8,
+    /// 1 if the secondary channel is above the primary, 3 if below and 0 if there is none
+    pub secondary_offset: u8,
+    /// Whether channels wider than 20 MHz may be used
+    pub any_width: bool
+}
+impl HtOperation {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 22 {
+            return Err(Error::UnexpectedEof)
+        }
+        Ok(Self {
+            primary_channel: data[0],
+            secondary_offset: data[1] & 0b11,
+            any_width: data[1] & 0b100 != 0
+        })
+    }
+    pub fn width(&self) -> ChannelWidth {
+        if self.any_width && self.secondary_offset != 0 { ChannelWidth::Mhz40 } else { ChannelWidth::Mhz20 }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VhtCapabilities {
+    pub info: u32,
+    pub rx_mcs_map: u16
+}
+impl VhtCapabilities {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 12 {
+            return Err(Error::UnexpectedEof)
+        }
+        Ok(Self {
+            info: u32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+            rx_mcs_map: u16::from_le_bytes([data[4], data[5]])
+        })
+    }
+    pub fn spatial_streams(&self) -> u8 {
+        mcs_map_streams(self.rx_mcs_map)
+    }
+    pub fn width(&self) -> ChannelWidth {
+        match (self.info >> 2) & 0b11 {
+            0 => ChannelWidth::Mhz80,
+            _ => ChannelWidth::Mhz160
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VhtOperation {
+    /// 0 defers to the HT operation element, 1 allows 80 MHz or wider
+    pub width: u8,
+    pub center0: u8,
+    pub center1: u8
+}
+impl VhtOperation {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 5 {
+            return Err(Error::UnexpectedEof)
+        }
+        Ok(Self {
+            width: data[0],
+            center0: data[1],
+            center1: data[2]
+        })
+    }
+    /// The operating width, or None if it is given by the HT operation element instead
+    pub fn width(&self) -> Option<ChannelWidth> {
+        match self.width {
+            0 => None,
+            1 => Some(ChannelWidth::from_segments(self.center0, self.center1)),
+            2 => Some(ChannelWidth::Mhz160),
+            _ => Some(ChannelWidth::Mhz80Plus80)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HeCapabilities {
+    pub mac: [u8; 6],
+    pub phy: [u8; 11],
+    /// The receive MCS map for channels up to 80 MHz
+    pub rx_mcs_map: u16
+}
+impl HeCapabilities {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 21 {
+            return Err(Error::UnexpectedEof)
+        }
+        let mut mac = [0; 6];
+        mac.copy_from_slice(&data[0..6]);
+        let mut phy = [0; 11];
+        phy.copy_from_slice(&data[6..17]);
+        Ok(Self {
+            mac,
+            phy,
+            rx_mcs_map: u16::from_le_bytes([data[17], data[18]])
+        })
+    }
+    pub fn spatial_streams(&self) -> u8 {
+        mcs_map_streams(self.rx_mcs_map)
+    }
+    pub fn width(&self) -> ChannelWidth {
+        let widths = self.phy[0];
+        if widths & 0b1000 != 0 {
+            ChannelWidth::Mhz160
+        } else if widths & 0b100 != 0 {
+            ChannelWidth::Mhz80
+        } else if widths & 0b10 != 0 {
+            ChannelWidth::Mhz40
+        } else {
+            ChannelWidth::Mhz20
+        }
+    }
+}
+
+/// The operating channel of an HE BSS on the 6 GHz band
+#[derive(Debug, Clone)]
+pub struct SixGhzOperation {
+    pub primary_channel: u8,
+    pub width: ChannelWidth
+}
+
+#[derive(Debug, Clone)]
+pub struct HeOperation {
+    pub parameters: u32,
+    pub bss_color: u8,
+    pub six_ghz: Option<SixGhzOperation>
+}
+impl HeOperation {
+    const VHT_OPERATION_PRESENT: u32 = 1 << 14;
+    const CO_HOSTED_BSS: u32 = 1 << 15;
+    const SIX_GHZ_OPERATION_PRESENT: u32 = 1 << 17;
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 6 {
+            return Err(Error::UnexpectedEof)
+        }
+        let parameters = u32::from_le_bytes([data[0], data[1], data[2], 0]);
+        // Skip the optional fields that come before the 6 GHz operation information
+        let mut offset = 6;
+        if parameters & Self::VHT_OPERATION_PRESENT != 0 {
+            offset += 3
+        }
+        if parameters & Self::CO_HOSTED_BSS != 0 {
+            offset += 1
+        }
+        let six_ghz = if parameters & Self::SIX_GHZ_OPERATION_PRESENT != 0 {
+            let info = data.get(offset..offset + 5).ok_or(Error::UnexpectedEof)?;
+            Some(SixGhzOperation {
+                primary_channel: info[0],
+                width: match info[1] & 0b11 {
+                    0 => ChannelWidth::Mhz20,
+                    1 => ChannelWidth::Mhz40,
+                    2 => ChannelWidth::Mhz80,
+                    _ => match ChannelWidth::from_segments(info[2], info[3]) {
+                        ChannelWidth::Mhz80Plus80 => ChannelWidth::Mhz80Plus80,
+                        _ => ChannelWidth::Mhz160
+                    }
+                }
+            })
+        } else {
+            None
+        };
+        Ok(Self {
+            parameters,
+            bss_color: data[3] & 0b111111,
+            six_ghz
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EhtCapabilities {
+    pub mac: u16,
+    pub phy: [u8; 9]
+}
+impl EhtCapabilities {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 11 {
+            return Err(Error::UnexpectedEof)
+        }
+        let mut phy = [0; 9];
+        phy.copy_from_slice(&data[2..11]);
+        Ok(Self {
+            mac: u16::from_le_bytes([data[0], data[1]]),
+            phy
+        })
+    }
+    pub fn supports_320mhz(&self) -> bool {
+        self.phy[0] & 0b10 != 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EhtOperation {
+    pub parameters: u8,
+    /// The operating width, if it differs from the width given by the HT, VHT and HE elements
+    pub width: Option<ChannelWidth>
+}
+impl EhtOperation {
+    const INFORMATION_PRESENT: u8 = 1;
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 5 {
+            return Err(Error::UnexpectedEof)
+        }
+        let parameters = data[0];
+        let width = if parameters & Self::INFORMATION_PRESENT != 0 {
+            let &control = data.get(5).ok_or(Error::UnexpectedEof)?;
+            Some(match control & 0b111 {
+                0 => ChannelWidth::Mhz20,
+                1 => ChannelWidth::Mhz40,
+                2 => ChannelWidth::Mhz80,
+                3 => ChannelWidth::Mhz160,
+                _ => ChannelWidth::Mhz320
+            })
+        } else {
+            None
+        };
+        Ok(Self {
+            parameters,
+            width
+        })
+    }
+}
+
+/// The physical layer capabilities of a device, as advertised in its management frames
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhyProfile {
+    pub generation: Generation,
+    pub spatial_streams: u8,
+    /// The operating width of an access point, or the widest supported width for a client
+    pub width: ChannelWidth
+}
+impl PhyProfile {
+    pub fn new(tags: &[ManagementTag]) -> Self {
+        let mut ht_capabilities = None;
+        let mut ht_operation = None;
+        let mut vht_capabilities = None;
+        let mut vht_operation = None;
+        let mut he_capabilities = None;
+        let mut he_operation = None;
+        let mut he_six_ghz = false;
+        let mut eht_capabilities = None;
+        let mut eht_operation = None;
+        for tag in tags {
+            match tag {
+                ManagementTag::HtCapabilities(t) => ht_capabilities = Some(t),
+                ManagementTag::HtOperation(t) => ht_operation = Some(t),
+                ManagementTag::VhtCapabilities(t) => vht_capabilities = Some(t),
+                ManagementTag::VhtOperation(t) => vht_operation = Some(t),
+                ManagementTag::HeCapabilities(t) => he_capabilities = Some(t),
+                ManagementTag::HeOperation(t) => he_operation = Some(t),
+                ManagementTag::He6GhzCapabilities => he_six_ghz = true,
+                ManagementTag::EhtCapabilities(t) => eht_capabilities = Some(t),
+                ManagementTag::EhtOperation(t) => eht_operation = Some(t),
+                _ => ()
+            }
+        }
+        let six_ghz = he_six_ghz || he_operation.is_some_and(|op| op.six_ghz.is_some());
+
+        let generation = if eht_capabilities.is_some() || eht_operation.is_some() {
+            Generation::WiFi7
+        } else if six_ghz {
+            Generation::WiFi6E
+        } else if he_capabilities.is_some() || he_operation.is_some() {
+            Generation::WiFi6
+        } else if vht_capabilities.is_some() || vht_operation.is_some() {
+            Generation::WiFi5
+        } else if ht_capabilities.is_some() || ht_operation.is_some() {
+            Generation::WiFi4
+        } else {
+            Generation::Legacy
+        };
+
+        let spatial_streams = [
+            ht_capabilities.map(HtCapabilities::spatial_streams),
+            vht_capabilities.map(VhtCapabilities::spatial_streams),
+            he_capabilities.map(HeCapabilities::spatial_streams)
+        ].iter().flatten().copied().max().unwrap_or(1).max(1);
+
+        // Operation elements describe the channel in use, and are only sent by access points
+        let operating = eht_operation.and_then(|op| op.width)
+            .or_else(|| he_operation.and_then(|op| op.six_ghz.as_ref()).map(|op| op.width))
+            .or_else(|| vht_operation.and_then(VhtOperation::width))
+            .or_else(|| ht_operation.map(HtOperation::width));
+        let width = operating.unwrap_or_else(|| if eht_capabilities.is_some_and(EhtCapabilities::supports_320mhz) {
+            ChannelWidth::Mhz320
+        } else {
+            [
+                ht_capabilities.map(HtCapabilities::width),
+                vht_capabilities.map(VhtCapabilities::width),
+                he_capabilities.map(HeCapabilities::width)
+            ].iter().flatten().copied().max().unwrap_or(ChannelWidth::Mhz20)
+        });
+
+        Self {
+            generation,
+            spatial_streams,
+            width
+        }
+    }
+}
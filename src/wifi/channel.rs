@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+use super::ManagementTag;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Band {
+    /// 802.11ah and other sub-1 GHz channels
+    Mhz900,
+    Ghz2_4,
+    Ghz5,
+    Ghz6
+}
+impl std::fmt::Display for Band {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Mhz900 => write!(f, "900 MHz"),
+            Self::Ghz2_4 => write!(f, "2.4 GHz"),
+            Self::Ghz5 => write!(f, "5 GHz"),
+            Self::Ghz6 => write!(f, "6 GHz")
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Channel {
+    pub band: Band,
+    pub number: u8
+}
+impl Channel {
+    /// Find the channel with the given centre frequency in MHz
+    pub fn from_frequency(freq: u16) -> Option<Self> {
+        let (band, number) = match freq {
+            902..=928 => (Band::Mhz900, (freq - 902) * 2),
+            2484 => (Band::Ghz2_4, 14),
+            2412..=2472 => (Band::Ghz2_4, (freq - 2407) / 5),
+            // 4.9 GHz public safety channels are numbered from 4 GHz
+            4910..=4980 => (Band::Ghz5, (freq - 4000) / 5),
+            5935 => (Band::Ghz6, 2),
+            5955..=7115 => (Band::Ghz6, (freq - 5950) / 5),
+            5000..=5925 => (Band::Ghz5, (freq - 5000) / 5),
+            _ => return None
+        };
+        Some(Self {
+            band,
+            number: number as u8
+        })
+    }
+    /// Find the channel an access point operates on from its beacon or probe response
+    ///
+    /// The channel a frame is heard on may differ due to leakage from adjacent channels, so the heard channel is only used for its band.
+    pub fn operating(tags: &[ManagementTag], heard: Option<Self>) -> Option<Self> {
+        let mut ds = None;
+        let mut ht = None;
+        let mut six_ghz = None;
+        for tag in tags {
+            match tag {
+                ManagementTag::DsParameterSet { channel } => ds = Some(*channel),
+                ManagementTag::HtOperation(operation) => ht = Some(operation.primary_channel),
+                ManagementTag::HeOperation(operation) => six_ghz = operation.six_ghz.as_ref().map(|op| op.primary_channel),
+                _ => ()
+            }
+        }
+        if let Some(number) = six_ghz {
+            return Some(Self { band: Band::Ghz6, number })
+        }
+        let number = ht.or(ds)?;
+        let band = match heard {
+            Some(heard) => heard.band,
+            // Channel numbers up to 14 are only used by 2.4 GHz when the band is unknown
+            None if number <= 14 => Band::Ghz2_4,
+            None => Band::Ghz5
+        };
+        Some(Self { band, number })
+    }
+}
+impl std::fmt::Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} ({})", self.number, self.band)
+    }
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+const SUPPORTED_RATES: u8 = 0x01;
+const HT_CAPABILITIES: u8 = 0x2d;
+const EXTENDED_SUPPORTED_RATES: u8 = 0x32;
+const EXTENDED_CAPABILITIES: u8 = 0x7f;
+const VHT_CAPABILITIES: u8 = 0xbf;
+const VENDOR_SPECIFIC: u8 = 0xdd;
+const EXTENSION: u8 = 0xff;
+
+/// A hash of how a device builds its probe requests, which tells apart makes, models and drivers even behind a randomised address
+///
+/// The order of the elements is hashed along with the supported rates, the HT, VHT and extended capabilities and the vendor of each vendor specific element.
+/// Anything that changes from one probe to the next, like the SSID or the current channel, is left out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Fingerprint(pub u32);
+impl Fingerprint {
+    /// Hash the raw tagged parameters of a probe request, ignoring a truncated element at the end
+    pub fn new(mut data: &[u8]) -> Self {
+        let mut hasher = crc32fast::Hasher::new();
+        while let [id, length, rest @ ..] = data {
+            let length = *length as usize;
+            if rest.len() < length {
+                break
+            }
+            let (body, next) = rest.split_at(length);
+            data = next;
+            match *id {
+                SUPPORTED_RATES | EXTENDED_SUPPORTED_RATES | HT_CAPABILITIES | VHT_CAPABILITIES | EXTENDED_CAPABILITIES => {
+                    hasher.update(&[*id, length as u8]);
+                    hasher.update(body)
+                },
+                // The OUI and type identify the element, while the rest may hold per-probe data
+                VENDOR_SPECIFIC => {
+                    hasher.update(&[*id]);
+                    hasher.update(&body[..body.len().min(4)])
+                },
+                // The first byte is the real element ID
+                EXTENSION => {
+                    hasher.update(&[*id]);
+                    hasher.update(&body[..body.len().min(1)])
+                },
+                _ => hasher.update(&[*id])
+            }
+        }
+        Self(hasher.finalize())
+    }
+}
+impl std::fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:08x}", self.0)
+    }
+}
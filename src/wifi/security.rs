@@ -0,0 +1,435 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Error, ManagementTag, Result};
+
+/// The OUI used by suites defined in the 802.11 standard
+const IEEE_OUI: [u8; 3] = [0x00, 0x0f, 0xac];
+/// The OUI used by suites in the pre-standard Microsoft WPA element
+pub const WPA_OUI: [u8; 3] = [0x00, 0x50, 0xf2];
+
+/// The privacy bit of the capability information field
+const CAPABILITY_PRIVACY: u16 = 1 << 4;
+/// Management frame protection bits of the RSN capabilities
+const MFP_REQUIRED: u16 = 1 << 6;
+const MFP_CAPABLE: u16 = 1 << 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CipherSuite {
+    UseGroup,
+    Wep40,
+    Tkip,
+    Ccmp128,
+    Wep104,
+    BipCmac128,
+    GroupNotAllowed,
+    Gcmp128,
+    Gcmp256,
+    Ccmp256,
+    BipGmac128,
+    BipGmac256,
+    BipCmac256,
+    Other(u8),
+    Vendor([u8; 3], u8)
+}
+impl CipherSuite {
+    fn new(oui: [u8; 3], suite: [u8; 4]) -> Self {
+        if suite[..3] != oui {
+            return Self::Vendor([suite[0], suite[1], suite[2]], suite[3])
+        }
+        match suite[3] {
+            0 => Self::UseGroup,
+            1 => Self::Wep40,
+            2 => Self::Tkip,
+            4 => Self::Ccmp128,
+            5 => Self::Wep104,
+            6 => Self::BipCmac128,
+            7 => Self::GroupNotAllowed,
+            8 => Self::Gcmp128,
+            9 => Self::Gcmp256,
+            10 => Self::Ccmp256,
+            11 => Self::BipGmac128,
+            12 => Self::BipGmac256,
+            13 => Self::BipCmac256,
+            other => Self::Other(other)
+        }
+    }
+}
+impl std::fmt::Display for CipherSuite {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::UseGroup => write!(f, "Group"),
+            Self::Wep40 => write!(f, "WEP-40"),
+            Self::Tkip => write!(f, "TKIP"),
+            Self::Ccmp128 => write!(f, "CCMP"),
+            Self::Wep104 => write!(f, "WEP-104"),
+            Self::BipCmac128 => write!(f, "BIP-CMAC-128"),
+            Self::GroupNotAllowed => write!(f, "Group Not Allowed"),
+            Self::Gcmp128 => write!(f, "GCMP-128"),
+            Self::Gcmp256 => write!(f, "GCMP-256"),
+            Self::Ccmp256 => write!(f, "CCMP-256"),
+            Self::BipGmac128 => write!(f, "BIP-GMAC-128"),
+            Self::BipGmac256 => write!(f, "BIP-GMAC-256"),
+            Self::BipCmac256 => write!(f, "BIP-CMAC-256"),
+            Self::Other(suite) => write!(f, "Unknown ({})", suite),
+            Self::Vendor(oui, suite) => write!(f, "Vendor {:02x}:{:02x}:{:02x} ({})", oui[0], oui[1], oui[2], suite)
+        }
+    }
+}
+
+/// An authentication and key management suite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Akm {
+    Ieee8021x,
+    Psk,
+    FtIeee8021x,
+    FtPsk,
+    Ieee8021xSha256,
+    PskSha256,
+    Tdls,
+    Sae,
+    FtSae,
+    ApPeerKey,
+    SuiteB,
+    SuiteB192,
+    FtIeee8021xSha384,
+    FilsSha256,
+    FilsSha384,
+    FtFilsSha256,
+    FtFilsSha384,
+    Owe,
+    FtPskSha384,
+    PskSha384,
+    SaeExtKey,
+    FtSaeExtKey,
+    Other(u8),
+    Vendor([u8; 3], u8)
+}
+impl Akm {
+    fn new(oui: [u8; 3], suite: [u8; 4]) -> Self {
+        if suite[..3] != oui {
+            return Self::Vendor([suite[0], suite[1], suite[2]], suite[3])
+        }
+        match suite[3] {
+            1 => Self::Ieee8021x,
+            2 => Self::Psk,
+            3 => Self::FtIeee8021x,
+            4 => Self::FtPsk,
+            5 => Self::Ieee8021xSha256,
+            6 => Self::PskSha256,
+            7 => Self::Tdls,
+            8 => Self::Sae,
+            9 => Self::FtSae,
+            10 => Self::ApPeerKey,
+            11 => Self::SuiteB,
+            12 => Self::SuiteB192,
+            13 => Self::FtIeee8021xSha384,
+            14 => Self::FilsSha256,
+            15 => Self::FilsSha384,
+            16 => Self::FtFilsSha256,
+            17 => Self::FtFilsSha384,
+            18 => Self::Owe,
+            19 => Self::FtPskSha384,
+            20 => Self::PskSha384,
+            24 => Self::SaeExtKey,
+            25 => Self::FtSaeExtKey,
+            other => Self::Other(other)
+        }
+    }
+    /// Whether the suite authenticates users through 802.1X
+    pub fn enterprise(self) -> bool {
+        matches!(self,
+            Self::Ieee8021x | Self::FtIeee8021x | Self::Ieee8021xSha256 | Self::SuiteB | Self::SuiteB192
+                | Self::FtIeee8021xSha384 | Self::FilsSha256 | Self::FilsSha384 | Self::FtFilsSha256 | Self::FtFilsSha384
+        )
+    }
+    /// Whether the suite uses a pre-shared key
+    pub fn psk(self) -> bool {
+        matches!(self, Self::Psk | Self::FtPsk | Self::PskSha256 | Self::FtPskSha384 | Self::PskSha384)
+    }
+    /// Whether the suite uses simultaneous authentication of equals
+    pub fn sae(self) -> bool {
+        matches!(self, Self::Sae | Self::FtSae | Self::SaeExtKey | Self::FtSaeExtKey)
+    }
+}
+impl std::fmt::Display for Akm {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Ieee8021x => write!(f, "802.1X"),
+            Self::Psk => write!(f, "PSK"),
+            Self::FtIeee8021x => write!(f, "FT-802.1X"),
+            Self::FtPsk => write!(f, "FT-PSK"),
+            Self::Ieee8021xSha256 => write!(f, "802.1X-SHA256"),
+            Self::PskSha256 => write!(f, "PSK-SHA256"),
+            Self::Tdls => write!(f, "TDLS"),
+            Self::Sae => write!(f, "SAE"),
+            Self::FtSae => write!(f, "FT-SAE"),
+            Self::ApPeerKey => write!(f, "AP PeerKey"),
+            Self::SuiteB => write!(f, "Suite B"),
+            Self::SuiteB192 => write!(f, "Suite B 192"),
+            Self::FtIeee8021xSha384 => write!(f, "FT-802.1X-SHA384"),
+            Self::FilsSha256 => write!(f, "FILS-SHA256"),
+            Self::FilsSha384 => write!(f, "FILS-SHA384"),
+            Self::FtFilsSha256 => write!(f, "FT-FILS-SHA256"),
+            Self::FtFilsSha384 => write!(f, "FT-FILS-SHA384"),
+            Self::Owe => write!(f, "OWE"),
+            Self::FtPskSha384 => write!(f, "FT-PSK-SHA384"),
+            Self::PskSha384 => write!(f, "PSK-SHA384"),
+            Self::SaeExtKey => write!(f, "SAE-EXT-KEY"),
+            Self::FtSaeExtKey => write!(f, "FT-SAE-EXT-KEY"),
+            Self::Other(suite) => write!(f, "Unknown ({})", suite),
+            Self::Vendor(oui, suite) => write!(f, "Vendor {:02x}:{:02x}:{:02x} ({})", oui[0], oui[1], oui[2], suite)
+        }
+    }
+}
+
+/// The contents of an RSN element, or the older WPA vendor element which shares its layout
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rsn {
+    pub version: u16,
+    pub group_cipher: CipherSuite,
+    pub pairwise_ciphers: Vec<CipherSuite>,
+    pub akm_suites: Vec<Akm>,
+    pub capabilities: u16,
+    /// The cipher protecting group addressed management frames, only present in RSN elements
+    pub group_management_cipher: Option<CipherSuite>
+}
+impl Rsn {
+    /// Parse the body of an RSN element
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        Self::parse_with(data, IEEE_OUI)
+    }
+    /// Parse the body of a WPA element following the vendor OUI and type
+    pub fn parse_wpa(data: &[u8]) -> Result<Self> {
+        Self::parse_with(data, WPA_OUI)
+    }
+    fn parse_with(mut data: &[u8], oui: [u8; 3]) -> Result<Self> {
+        fn take<'a>(data: &mut &'a [u8], count: usize) -> Result<&'a [u8]> {
+            if data.len() < count {
+                return Err(Error::UnexpectedEof)
+            }
+            let (taken, rest) = data.split_at(count);
+            *data = rest;
+            Ok(taken)
+        }
+        fn read_u16(data: &mut &[u8]) -> Result<u16> {
+            take(data, 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+        }
+        fn read_suite(data: &mut &[u8]) -> Result<[u8; 4]> {
+            take(data, 4).map(|b| [b[0], b[1], b[2], b[3]])
+        }
+        fn read_suites(data: &mut &[u8]) -> Result<Vec<[u8; 4]>> {
+            let count = read_u16(data)?;
+            (0..count).map(|_| read_suite(data)).collect()
+        }
+
+        let version = read_u16(&mut data)?;
+        // Every field after the version may be left out, in which case the defaults apply
+        let default_cipher = if oui == WPA_OUI { CipherSuite::Tkip } else { CipherSuite::Ccmp128 };
+        let group_cipher = if data.is_empty() { default_cipher } else { CipherSuite::new(oui, read_suite(&mut data)?) };
+        let pairwise_ciphers = if data.is_empty() {
+            vec![default_cipher]
+        } else {
+            read_suites(&mut data)?.into_iter().map(|s| CipherSuite::new(oui, s)).collect()
+        };
+        let akm_suites = if data.is_empty() {
+            vec![Akm::Ieee8021x]
+        } else {
+            read_suites(&mut data)?.into_iter().map(|s| Akm::new(oui, s)).collect()
+        };
+        let capabilities = if data.is_empty() { 0 } else { read_u16(&mut data)? };
+        let group_management_cipher = if oui == WPA_OUI || data.is_empty() {
+            None
+        } else {
+            // Skip over the PMKIDs
+            let count = read_u16(&mut data)?;
+            take(&mut data, count as usize * 16)?;
+            if data.is_empty() { None } else { Some(CipherSuite::new(oui, read_suite(&mut data)?)) }
+        };
+        Ok(Self {
+            version,
+            group_cipher,
+            pairwise_ciphers,
+            akm_suites,
+            capabilities,
+            group_management_cipher
+        })
+    }
+    /// Whether management frame protection must be used to join
+    pub fn mfp_required(&self) -> bool {
+        self.capabilities & MFP_REQUIRED != 0
+    }
+    /// Whether management frame protection may be used
+    pub fn mfp_capable(&self) -> bool {
+        self.capabilities & MFP_CAPABLE != 0
+    }
+}
+
+/// A summary of the security used by a network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Security {
+    Open,
+    Wep,
+    Wpa,
+    Wpa2,
+    Wpa3,
+    /// Mixed WPA2 and WPA3 personal, allowing both PSK and SAE
+    Transition,
+    Enterprise,
+    /// WPA of some kind, advertised in an element that could not be decoded
+    Malformed
+}
+impl std::fmt::Display for Security {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Open => write!(f, "Open"),
+            Self::Wep => write!(f, "WEP"),
+            Self::Wpa => write!(f, "WPA"),
+            Self::Wpa2 => write!(f, "WPA2"),
+            Self::Wpa3 => write!(f, "WPA3"),
+            Self::Transition => write!(f, "WPA2/WPA3 Transition"),
+            Self::Enterprise => write!(f, "Enterprise"),
+            Self::Malformed => write!(f, "WPA (Malformed)")
+        }
+    }
+}
+
+/// The security advertised in a beacon or probe response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityProfile {
+    pub summary: Security,
+    /// The RSN element, or the WPA element for networks without one
+    pub rsn: Option<Rsn>
+}
+impl SecurityProfile {
+    pub fn new(capabilities: u16, tags: &[ManagementTag]) -> Self {
+        let rsn = tags.iter().find_map(|t| if let ManagementTag::Rsn(rsn) = t { Some(rsn) } else { None });
+        let wpa = tags.iter().find_map(|t| if let ManagementTag::Wpa(wpa) = t { Some(wpa) } else { None });
+        let malformed = tags.iter().any(|t| matches!(t, ManagementTag::MalformedRsn));
+        let summary = match (rsn, wpa) {
+            (Some(rsn), _) => {
+                let any = |f: fn(Akm) -> bool| rsn.akm_suites.iter().any(|&akm| f(akm));
+                if any(Akm::enterprise) {
+                    Security::Enterprise
+                } else if any(Akm::sae) && any(Akm::psk) {
+                    Security::Transition
+                } else if any(Akm::sae) || any(|akm| akm == Akm::Owe) {
+                    Security::Wpa3
+                } else {
+                    Security::Wpa2
+                }
+            },
+            // A damaged RSN element may be hiding anything newer than WPA
+            _ if malformed => Security::Malformed,
+            (None, Some(wpa)) if wpa.akm_suites.iter().any(|&akm| akm.enterprise()) => Security::Enterprise,
+            (None, Some(_)) => Security::Wpa,
+            (None, None) if capabilities & CAPABILITY_PRIVACY != 0 => Security::Wep,
+            (None, None) => Security::Open
+        };
+        Self {
+            summary,
+            rsn: rsn.or(wpa).cloned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An SSID element for "test" that starts every set of tags
+    const SSID: [u8; 6] = [0x00, 0x04, b't', b'e', b's', b't'];
+    const WPA2_PSK: [u8; 22] = [
+        0x30, 20,
+        0x01, 0x00,
+        0x00, 0x0f, 0xac, 0x04,
+        0x01, 0x00, 0x00, 0x0f, 0xac, 0x04,
+        0x01, 0x00, 0x00, 0x0f, 0xac, 0x02,
+        0x00, 0x00
+    ];
+    const WPA_PSK: [u8; 24] = [
+        0xdd, 22,
+        0x00, 0x50, 0xf2, 0x01,
+        0x01, 0x00,
+        0x00, 0x50, 0xf2, 0x02,
+        0x01, 0x00, 0x00, 0x50, 0xf2, 0x02,
+        0x01, 0x00, 0x00, 0x50, 0xf2, 0x02
+    ];
+
+    fn profile(elements: &[&[u8]]) -> SecurityProfile {
+        let data: Vec<u8> = elements.concat();
+        let tags = ManagementTag::parse_all(&data).expect("Tags should parse");
+        SecurityProfile::new(CAPABILITY_PRIVACY, &tags)
+    }
+
+    #[test]
+    fn wpa2_psk() {
+        let profile = profile(&[&SSID, &WPA2_PSK]);
+        assert_eq!(profile.summary, Security::Wpa2);
+        let rsn = profile.rsn.unwrap();
+        assert_eq!(rsn.version, 1);
+        assert_eq!(rsn.group_cipher, CipherSuite::Ccmp128);
+        assert_eq!(rsn.pairwise_ciphers, vec![CipherSuite::Ccmp128]);
+        assert_eq!(rsn.akm_suites, vec![Akm::Psk]);
+        assert!(!rsn.mfp_capable());
+        assert!(!rsn.mfp_required());
+        assert_eq!(rsn.group_management_cipher, None);
+    }
+
+    #[test]
+    fn wpa3_sae_with_mfp_required() {
+        let sae = [
+            0x30, 26,
+            0x01, 0x00,
+            0x00, 0x0f, 0xac, 0x04,
+            0x01, 0x00, 0x00, 0x0f, 0xac, 0x04,
+            0x01, 0x00, 0x00, 0x0f, 0xac, 0x08,
+            0xc0, 0x00,
+            // No PMKIDs, then the group management cipher
+            0x00, 0x00,
+            0x00, 0x0f, 0xac, 0x06
+        ];
+        let profile = profile(&[&SSID, &sae]);
+        assert_eq!(profile.summary, Security::Wpa3);
+        let rsn = profile.rsn.unwrap();
+        assert_eq!(rsn.akm_suites, vec![Akm::Sae]);
+        assert!(rsn.mfp_capable());
+        assert!(rsn.mfp_required());
+        assert_eq!(rsn.group_management_cipher, Some(CipherSuite::BipCmac128));
+    }
+
+    #[test]
+    fn mixed_wpa_and_wpa2() {
+        let mixed = profile(&[&SSID, &WPA_PSK, &WPA2_PSK]);
+        assert_eq!(mixed.summary, Security::Wpa2);
+        // The RSN element is preferred over the older WPA element
+        assert_eq!(mixed.rsn.unwrap().group_cipher, CipherSuite::Ccmp128);
+
+        let wpa_only = profile(&[&SSID, &WPA_PSK]);
+        assert_eq!(wpa_only.summary, Security::Wpa);
+        let wpa = wpa_only.rsn.unwrap();
+        assert_eq!(wpa.group_cipher, CipherSuite::Tkip);
+        assert_eq!(wpa.pairwise_ciphers, vec![CipherSuite::Tkip]);
+        assert_eq!(wpa.akm_suites, vec![Akm::Psk]);
+    }
+
+    #[test]
+    fn truncated_rsn() {
+        // The pairwise cipher count is cut short
+        let truncated = [0x30, 7, 0x01, 0x00, 0x00, 0x0f, 0xac, 0x04, 0x01];
+        assert!(Rsn::parse(&truncated[2..]).is_err());
+        // The rest of the frame is still usable
+        let data = [&SSID[..], &truncated].concat();
+        let tags = ManagementTag::parse_all(&data).expect("A bad element should not fail the frame");
+        assert!(matches!(tags.as_slice(), [ManagementTag::Ssid(ssid), ManagementTag::MalformedRsn] if ssid == "test"));
+        // The network must not be mistaken for WEP just because its RSN element is damaged
+        let profile = SecurityProfile::new(CAPABILITY_PRIVACY, &tags);
+        assert_eq!(profile.summary, Security::Malformed);
+        assert!(profile.rsn.is_none());
+        // A readable WPA element alongside it does not hide that something newer may be in use
+        let data = [&SSID[..], &WPA_PSK, &truncated].concat();
+        let tags = ManagementTag::parse_all(&data).unwrap();
+        assert_eq!(SecurityProfile::new(CAPABILITY_PRIVACY, &tags).summary, Security::Malformed);
+        // An element running past the end of the frame is still an error
+        assert!(ManagementTag::parse_all(&truncated[..5]).is_err());
+    }
+}